@@ -0,0 +1,23 @@
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+// Embed what -Xversion reports about the build itself.
+fn main() {
+    let target = env::var("TARGET").unwrap_or_else(|_| "unknown".to_string());
+    let revision = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+
+    println!("cargo:rustc-env=JRUBY_LAUNCHER_TARGET={}", target);
+    println!("cargo:rustc-env=JRUBY_LAUNCHER_GIT_REVISION={}", revision);
+    // A packaged source tree has no .git and naming missing paths reruns this every build.
+    if Path::new(".git").exists() {
+        println!("cargo:rerun-if-changed=.git/HEAD");
+        println!("cargo:rerun-if-changed=.git/refs");
+    }
+}
@@ -0,0 +1,109 @@
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use log::info;
+use crate::os_string_ext::OsStringExt;
+
+/// How deep @files may include other @files (mostly so a file including itself stops).
+pub const MAX_ARGFILE_DEPTH: usize = 8;
+
+/// Is `argument` an @file (or an @@ escaped literal @)?
+pub(crate) fn is_argfile(argument: &OsString) -> bool {
+    let argument = argument.to_string_lossy();
+
+    argument.len() > 1 && argument.starts_with('@')
+}
+
+/// Replace `@file` with the arguments in file.  Files hold arguments separated by whitespace,
+/// quoted like a POSIX shell, with # comments.  `@@arg` is the literal argument `@arg` and
+/// nothing after `--` in a file is expanded.  Relative paths are from `current_dir`.
+pub(crate) fn expand(argument: &OsString, current_dir: Option<&Path>) -> Result<Vec<OsString>, String> {
+    expand_at_depth(argument, current_dir, 1)
+}
+
+fn expand_at_depth(argument: &OsString, current_dir: Option<&Path>, depth: usize) -> Result<Vec<OsString>, String> {
+    let (_, rest) = argument.split_at(1);
+
+    if rest.starts_with(OsString::from("@")) {
+        return Ok(vec![rest]);
+    }
+
+    if depth > MAX_ARGFILE_DEPTH {
+        return Err(format!("{} is nested more than {} argument files deep", argument.to_string_lossy(), MAX_ARGFILE_DEPTH));
+    }
+
+    let file = match current_dir {
+        Some(dir) => dir.join(&rest),
+        None => PathBuf::from(&rest),
+    };
+    info!("Expanding argument file {:?}", file);
+
+    let contents = read_os_string(&file)
+        .map_err(|e| format!("unable to read argument file {}: {}", file.display(), e))?;
+    let words = contents.shell_split_commented()
+        .map_err(|e| format!("argument file {}: {}", file.display(), e))?;
+
+    let mut arguments = vec![];
+    let mut words = words.into_iter();
+
+    while let Some(word) = words.next() {
+        if word == "--" {
+            arguments.push(word);
+            arguments.extend(words);
+            break;
+        } else if is_argfile(&word) {
+            arguments.extend(expand_at_depth(&word, current_dir, depth + 1)?);
+        } else {
+            arguments.push(word);
+        }
+    }
+
+    Ok(arguments)
+}
+
+#[cfg(not(windows))]
+pub(crate) fn read_os_string(file: &Path) -> std::io::Result<OsString> {
+    use std::os::unix::ffi::OsStringExt;
+
+    fs::read(file).map(OsString::from_vec)
+}
+
+#[cfg(windows)]
+pub(crate) fn read_os_string(file: &Path) -> std::io::Result<OsString> {
+    fs::read_to_string(file).map(OsString::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsString;
+    use std::fs;
+    use crate::file_helper::tests::temp_dir;
+    use crate::argfile::{expand, is_argfile};
+
+    #[test]
+    fn test_is_argfile() {
+        assert!(is_argfile(&OsString::from("@opts.txt")));
+        assert!(is_argfile(&OsString::from("@@literal")));
+        assert!(!is_argfile(&OsString::from("@")));
+        assert!(!is_argfile(&OsString::from("-J@foo")));
+    }
+
+    #[test]
+    fn test_expand() {
+        let dir = temp_dir("argfile");
+        fs::create_dir_all(dir.join("conf")).unwrap();
+        fs::write(dir.join("opts.txt"), "# generated\n--dev -J-Xmx2g\n@conf/more.txt\n-J-Dname=\"My App\" @@at\n-- @not\n").unwrap();
+        fs::write(dir.join("conf").join("more.txt"), "-Xcp:a 'lib/a b.jar'  # extra jars\n").unwrap();
+        fs::write(dir.join("loop.txt"), "-v @loop.txt\n").unwrap();
+
+        let expanded = expand(&OsString::from("@opts.txt"), Some(&dir));
+        let escaped = expand(&OsString::from("@@opts.txt"), Some(&dir));
+        let looped = expand(&OsString::from("@loop.txt"), Some(&dir));
+        let missing = expand(&OsString::from("@missing.txt"), Some(&dir));
+
+        assert_eq!(expanded.unwrap(), vec!["--dev", "-J-Xmx2g", "-Xcp:a", "lib/a b.jar", "-J-Dname=My App", "@at", "--", "@not"]);
+        assert_eq!(escaped.unwrap(), vec!["@opts.txt"]);
+        assert!(looped.unwrap_err().contains("nested more than 8"));
+        assert!(missing.unwrap_err().contains("unable to read argument file"));
+    }
+}
@@ -1,12 +1,31 @@
 use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use log::{error, info};
-use crate::launch_options::LaunchError;
-use crate::file_helper::find_from_path;
+use crate::launch_options::{LaunchError, JRUBY_JARS};
+use crate::file_helper::{find_from_path, resolve_symlinks};
 use std::error::Error;
 use std::ffi::OsString;
 use process_path::get_executable_path;
 
+/// Environment variables the launcher honors (for -Xhelp).
+pub const ENVIRONMENT_VARIABLES: [(&str, &str); 15] = [
+    ("JRUBY_HOME", "where JRuby is installed"),
+    ("JRUBY_OPTS", "options for JRuby"),
+    ("JRUBY_JAVA_VERSION", "required Java version (like -Xjava-version)"),
+    ("JRUBY_JSA", "AppCDS archive to use"),
+    ("JAVACMD", "java executable to run"),
+    ("JAVA_HOME", "JDK to use when neither JAVACMD nor -Xjdkhome is given"),
+    ("JAVA_OPTS", "options for java"),
+    ("JAVA_MEM", "java heap option, e.g. -Xmx2g"),
+    ("JAVA_STACK", "java stack option, e.g. -Xss4m"),
+    ("JAVA_ENCODING", "when set do not force -Dfile.encoding=UTF-8 (macOS)"),
+    ("CLASSPATH", "classpath when -J-cp is not given"),
+    ("PATH", "searched for java as a last resort"),
+    ("HOME", "searched for JDKs (.sdkman, .asdf, .jdks, .jenv)"),
+    ("XDG_CACHE_HOME", "where JDK probe results are cached (default ~/.cache)"),
+    ("XDG_CONFIG_HOME", "where the user's jruby/launcher.toml is (default ~/.config)"),
+];
+
 /// Represents a wrapper around accessing the actual OS environment.
 ///
 /// We have this struct so that we can test without needing to actually
@@ -18,6 +37,7 @@ pub struct Environment {
     pub args: Vec<OsString>,
     pub classpath: Option<OsString>,
     pub current_dir: Option<PathBuf>,
+    pub home: Option<OsString>,
     pub java_cmd: Option<OsString>,
     pub java_encoding: Option<OsString>,
     pub java_home: Option<OsString>,
@@ -26,8 +46,11 @@ pub struct Environment {
     pub java_stack: Option<OsString>,
     pub jruby_opts: Option<OsString>,
     pub jruby_home: Option<OsString>,
+    pub jruby_java_version: Option<OsString>,
     pub jruby_jsa_file: Option<OsString>,
     pub path: Option<OsString>,
+    pub xdg_cache_home: Option<OsString>,
+    pub xdg_config_home: Option<OsString>,
 }
 
 impl Environment {
@@ -36,6 +59,7 @@ impl Environment {
             args,
             classpath: env::var_os("CLASSPATH"),
             current_dir: env::current_dir().ok(),
+            home: env::var_os("HOME").or_else(|| env::var_os("USERPROFILE")),
             java_cmd: env::var_os("JAVACMD"),
             java_encoding: env::var_os("JAVA_ENCODING"),
             java_home: env::var_os("JAVA_HOME"),
@@ -44,8 +68,11 @@ impl Environment {
             java_stack: env::var_os("JAVA_STACK"),
             jruby_opts: env::var_os("JRUBY_OPTS"),
             jruby_home: env::var_os("JRUBY_HOME"),
+            jruby_java_version: env::var_os("JRUBY_JAVA_VERSION"),
             path: env::var_os("PATH"),
             jruby_jsa_file: env::var_os("JRUBY_JSA"),
+            xdg_cache_home: env::var_os("XDG_CACHE_HOME"),
+            xdg_config_home: env::var_os("XDG_CONFIG_HOME"),
         }
     }
 
@@ -93,7 +120,7 @@ impl Environment {
         if !exist_test(&dir) {
             error!("Failue: '{:?}' does not exist", &dir);
             return Err(Box::new(LaunchError {
-                message: "unable to find JRuby home",
+                message: "unable to find JRuby home".to_string(),
             }));
         }
 
@@ -101,6 +128,30 @@ impl Environment {
         Ok(dir)
     }
 
+    /// Where is JRuby installed?
+    ///
+    /// The launcher may be reached through symlinks (e.g. /usr/local/bin/jruby ->
+    /// /opt/jruby-9.4/bin/jruby) so we resolve them and walk up until we find a directory
+    /// which looks like a JRuby home (has lib/jruby.jar or lib/jruby-complete.jar).
+    pub(crate) fn determine_jruby_home<T>(&self, exist_test: T) -> Result<PathBuf, Box<dyn Error>> where
+        T: Fn(&PathBuf) -> bool + Copy {
+        let executable = self.determine_jruby_executable(exist_test)?;
+        let resolved = resolve_symlinks(&executable);
+
+        for candidate in [&resolved, &executable].iter() {
+            if let Some(home) = find_jruby_home_above(candidate, exist_test) {
+                info!("Success: JRuby home is {:?}", &home);
+                return Ok(home);
+            }
+        }
+
+        error!("Failure: no JRuby home above {:?} (resolved {:?})", &executable, &resolved);
+        Err(Box::new(LaunchError {
+            message: format!("unable to find JRuby home: no directory above {} contains lib/jruby.jar or lib/jruby-complete.jar (set JRUBY_HOME to your JRuby install)",
+                             resolved.display()),
+        }))
+    }
+
     /// Return a possible JRUBY install home based on liklihood.
     ///  1. assume absolute path is launched from project dir
     ///  2. CWD + relative path
@@ -133,17 +184,28 @@ impl Environment {
     }
 }
 
+fn find_jruby_home_above<T>(executable: &Path, exist_test: T) -> Option<PathBuf> where
+    T: Fn(&PathBuf) -> bool {
+    executable
+        .ancestors()
+        .skip(1)
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .find(|dir| JRUBY_JARS.iter().any(|jar| exist_test(&dir.join("lib").join(jar))))
+        .map(|dir| dir.to_path_buf())
+}
+
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use std::ffi::OsString;
     use crate::environment::Environment;
     use std::path::{MAIN_SEPARATOR, PathBuf};
 
-    fn empty_env() -> Environment {
+    pub(crate) fn empty_env() -> Environment {
         Environment {
             args: vec![],
             classpath: None,
             current_dir: None,
+            home: None,
             java_cmd: None,
             java_encoding: None,
             java_home: None,
@@ -152,8 +214,11 @@ mod tests {
             java_stack: None,
             jruby_opts: None,
             jruby_home: None,
+            jruby_java_version: None,
             path: None,
             jruby_jsa_file: None,
+            xdg_cache_home: None,
+            xdg_config_home: None,
         }
     }
 
@@ -218,4 +283,25 @@ mod tests {
         assert_eq!(env.derive_home_from_argv0(&argv0, &None, test).as_os_str(), &absolute);
 
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_determine_jruby_home_walks_up() {
+        let mut env = empty_env();
+        let home: PathBuf = [MAIN_SEPARATOR.to_string().as_str(), "opt", "jruby-9.4"].iter().collect();
+        let bin = home.join("bin");
+        let jar = home.join("lib").join("jruby-complete.jar");
+        env.jruby_home = Some(home.clone().into_os_string());
+        let test = |f: &PathBuf| f == &bin || f == &jar;
+
+        assert_eq!(env.determine_jruby_home(test).unwrap(), home);
+    }
+
+    #[test]
+    fn test_determine_jruby_home_not_found() {
+        let env = empty_env();
+        let test = |_f: &PathBuf| false;
+
+        let error = env.determine_jruby_home(test).unwrap_err();
+        assert!(error.to_string().contains("unable to find JRuby home"));
+    }
+}
@@ -1,8 +1,12 @@
 use std::env::split_paths;
 use std::ffi::OsString;
-use std::path::PathBuf;
+use std::fs;
+use std::path::{Path, PathBuf};
 use log::info;
 
+// Same limit most unix kernels use (MAXSYMLINKS) so a loop cannot hang us.
+const MAX_SYMLINK_HOPS: usize = 40;
+
 pub(crate) fn find_from_path<T>(file: &str, path: &Option<OsString>, test: T) -> Option<PathBuf> where
     T: Fn(&PathBuf) -> bool {
     if let Some(paths) = path {
@@ -19,3 +23,103 @@ pub(crate) fn find_from_path<T>(file: &str, path: &Option<OsString>, test: T) ->
 
     None
 }
+
+/// Follow a chain of symlinks (e.g. /usr/bin/java -> /etc/alternatives/java -> real jdk)
+/// to the real file.  Each hop is logged so -Xtrace shows how we got there.
+pub(crate) fn resolve_symlinks(path: &Path) -> PathBuf {
+    let mut current = path.to_path_buf();
+
+    for _ in 0..MAX_SYMLINK_HOPS {
+        match fs::read_link(&current) {
+            Ok(target) => {
+                let next = match current.parent() {
+                    Some(parent) if target.is_relative() => parent.join(&target),
+                    _ => target,
+                };
+                info!("resolve_symlinks: {:?} -> {:?}", &current, &next);
+                current = next;
+            }
+            Err(_) => break,
+        }
+    }
+
+    fs::canonicalize(&current).unwrap_or(current)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::fs;
+    use std::ops::Deref;
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use crate::file_helper::resolve_symlinks;
+
+    /// A fresh directory for a test's files which is removed when dropped, so a failing
+    /// assert does not leave it behind.
+    pub(crate) struct TempDir(PathBuf);
+
+    impl Deref for TempDir {
+        type Target = Path;
+
+        fn deref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl AsRef<Path> for TempDir {
+        fn as_ref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Unique per process and call so tests running in parallel never share one.  The path
+    /// is canonical since some temp dirs (macOS /var) are behind a symlink.
+    pub(crate) fn temp_dir(name: &str) -> TempDir {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir()
+            .join(format!("{}_{}_{}", name, std::process::id(), COUNT.fetch_add(1, Ordering::SeqCst)));
+
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        TempDir(fs::canonicalize(&dir).unwrap())
+    }
+
+    #[test]
+    fn test_resolve_symlinks_not_a_link() {
+        let dir = temp_dir("resolve_plain");
+        let file = dir.join("java");
+        fs::write(&file, "").unwrap();
+
+        let resolved = resolve_symlinks(&file);
+
+        assert_eq!(resolved.file_name(), file.file_name());
+        assert!(resolved.is_absolute());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_symlinks_chain() {
+        use std::os::unix::fs::symlink;
+
+        let dir = temp_dir("resolve_chain");
+        let jdk_bin = dir.join("jvm").join("jdk-17").join("bin");
+        let alternatives = dir.join("alternatives");
+        let usr_bin = dir.join("usr").join("bin");
+        fs::create_dir_all(&jdk_bin).unwrap();
+        fs::create_dir_all(&alternatives).unwrap();
+        fs::create_dir_all(&usr_bin).unwrap();
+        fs::write(jdk_bin.join("java"), "").unwrap();
+        symlink(jdk_bin.join("java"), alternatives.join("java")).unwrap();
+        symlink("../../alternatives/java", usr_bin.join("java")).unwrap();
+
+        let resolved = resolve_symlinks(&usr_bin.join("java"));
+
+        assert_eq!(resolved, jdk_bin.join("java"));
+    }
+}
@@ -0,0 +1,275 @@
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use log::info;
+use crate::java_installation::JavaInstallation;
+
+/// CPU architecture and C library of either this host or a Java install.  None means unknown
+/// and unknown never counts as a mismatch.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Platform {
+    pub arch: Option<String>,
+    pub libc: Option<String>,
+}
+
+impl Platform {
+    pub fn host() -> Platform {
+        Platform {
+            arch: Some(normalize_arch(std::env::consts::ARCH)),
+            libc: host_libc(),
+        }
+    }
+
+    /// Prefer what the release file says and fall back to the ELF header of bin/java.
+    pub fn of_java(installation: &JavaInstallation, java: &Path) -> Platform {
+        let mut platform = Platform {
+            arch: installation.os_arch.as_deref().map(normalize_arch),
+            libc: installation.libc.as_deref().and_then(normalize_libc),
+        };
+
+        if platform.arch.is_none() || (platform.libc.is_none() && cfg!(target_os = "linux")) {
+            if let Some(bytes) = read_head(java) {
+                let elf = elf_platform(&bytes);
+                platform.arch = platform.arch.or(elf.arch);
+                platform.libc = platform.libc.or(elf.libc);
+            }
+        }
+
+        platform
+    }
+
+    /// Describe why a Java for `self` cannot run on `host` (or None if it probably can).
+    pub fn mismatch(&self, host: &Platform) -> Option<String> {
+        if let (Some(java), Some(host)) = (&self.arch, &host.arch) {
+            if java != host {
+                return Some(format!("it is built for {} but this machine is {}", java, host));
+            }
+        }
+
+        if let (Some(java), Some(host)) = (&self.libc, &host.libc) {
+            if java != host {
+                return Some(format!("it needs {} libc but this machine uses {} libc", java, host));
+            }
+        }
+
+        None
+    }
+}
+
+/// Explain why the Java at `java` cannot run here, if it cannot.
+pub(crate) fn incompatibility(installation: &JavaInstallation, java: &Path) -> Option<String> {
+    let host = Platform::host();
+    let platform = Platform::of_java(installation, java);
+    info!("Host platform {:?}, Java platform {:?}", host, platform);
+
+    platform.mismatch(&host)
+}
+
+fn normalize_arch(arch: &str) -> String {
+    match arch {
+        "amd64" | "x86_64" | "x64" => "x86_64",
+        "aarch64" | "arm64" => "aarch64",
+        "x86" | "i386" | "i486" | "i586" | "i686" => "x86",
+        "ppc64le" | "powerpc64le" => "powerpc64le",
+        "ppc64" | "powerpc64" => "powerpc64",
+        other => other,
+    }.to_string()
+}
+
+// Older JDKs write LIBC="default" for glibc.
+fn normalize_libc(libc: &str) -> Option<String> {
+    match libc {
+        "musl" => Some("musl".to_string()),
+        "gnu" | "glibc" | "default" => Some("gnu".to_string()),
+        _ => None,
+    }
+}
+
+// A musl loader in /lib does not make a musl host (Debian's musl package installs one) so
+// ask a real binary which loader it uses instead.
+#[cfg(target_os = "linux")]
+fn host_libc() -> Option<String> {
+    ["/bin/sh", "/proc/self/exe"].iter()
+        .filter_map(|binary| read_head(Path::new(binary)))
+        .find_map(|bytes| elf_platform(&bytes).libc)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn host_libc() -> Option<String> {
+    None
+}
+
+const PT_INTERP: u32 = 3;
+
+/// The ELF and program headers plus the interpreter name sit at the front of an executable.
+const ELF_HEAD_SIZE: u64 = 4096;
+
+fn read_head(binary: &Path) -> Option<Vec<u8>> {
+    let mut bytes = vec![];
+
+    fs::File::open(binary).ok()?.take(ELF_HEAD_SIZE).read_to_end(&mut bytes).ok()?;
+    Some(bytes)
+}
+
+/// Read machine type and (from the program interpreter) libc out of an ELF executable.
+fn elf_platform(bytes: &[u8]) -> Platform {
+    let mut platform = Platform::default();
+
+    if bytes.len() < 64 || &bytes[0..4] != b"\x7fELF" {
+        return platform;
+    }
+
+    let is_64 = bytes[4] == 2;
+    let little_endian = bytes[5] == 1;
+    let read = |offset: usize, size: usize| -> Option<u64> {
+        let field = bytes.get(offset..offset.checked_add(size)?)?;
+        let value = if little_endian {
+            field.iter().rev().fold(0u64, |acc, b| (acc << 8) | *b as u64)
+        } else {
+            field.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64)
+        };
+        Some(value)
+    };
+
+    platform.arch = match read(18, 2) {
+        Some(3) => Some("x86"),
+        Some(62) => Some("x86_64"),
+        Some(183) => Some("aarch64"),
+        Some(40) => Some("arm"),
+        Some(21) if little_endian => Some("powerpc64le"),
+        Some(21) => Some("powerpc64"),
+        Some(22) => Some("s390x"),
+        Some(243) => Some("riscv64"),
+        _ => None,
+    }.map(|arch| arch.to_string());
+
+    let (phoff, phentsize, phnum) = if is_64 {
+        (read(32, 8), read(54, 2), read(56, 2))
+    } else {
+        (read(28, 4), read(42, 2), read(44, 2))
+    };
+
+    if let (Some(phoff), Some(phentsize), Some(phnum)) = (phoff, phentsize, phnum) {
+        for i in 0..phnum {
+            // Everything past here comes from the file so a damaged one must not overflow.
+            let header = match i.checked_mul(phentsize).and_then(|offset| offset.checked_add(phoff)) {
+                Some(header) if header < bytes.len() as u64 => header as usize,
+                _ => break,
+            };
+
+            if read(header, 4) != Some(PT_INTERP as u64) {
+                continue;
+            }
+
+            let (offset, size) = if is_64 {
+                (read(header + 8, 8), read(header + 32, 8))
+            } else {
+                (read(header + 4, 4), read(header + 16, 4))
+            };
+
+            let end = match (offset, size) {
+                (Some(offset), Some(size)) => offset.checked_add(size).map(|end| (offset, end)),
+                _ => None,
+            };
+
+            if let Some((offset, end)) = end {
+                if let Some(interpreter) = bytes.get(offset as usize..end as usize) {
+                    let interpreter = String::from_utf8_lossy(interpreter);
+                    info!("ELF interpreter: {}", interpreter.trim_end_matches('\0'));
+
+                    if interpreter.contains("musl") {
+                        platform.libc = Some("musl".to_string());
+                    } else if interpreter.contains("ld-linux") {
+                        platform.libc = Some("gnu".to_string());
+                    }
+                }
+            }
+        }
+    }
+
+    platform
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::java_compatibility::{elf_platform, host_libc, normalize_arch, normalize_libc, Platform};
+
+    fn platform(arch: &str, libc: &str) -> Platform {
+        Platform { arch: Some(arch.to_string()), libc: Some(libc.to_string()) }
+    }
+
+    fn platform_arch(arch: &str) -> Platform {
+        Platform { arch: Some(arch.to_string()), libc: None }
+    }
+
+    // Minimal 64-bit little endian ELF header with one PT_INTERP program header.
+    fn fake_elf(machine: u16, interpreter: &str) -> Vec<u8> {
+        let mut bytes = vec![0u8; 120];
+        bytes[0..4].copy_from_slice(b"\x7fELF");
+        bytes[4] = 2;
+        bytes[5] = 1;
+        bytes[18..20].copy_from_slice(&machine.to_le_bytes());
+        bytes[32..40].copy_from_slice(&64u64.to_le_bytes());
+        bytes[54..56].copy_from_slice(&56u16.to_le_bytes());
+        bytes[56..58].copy_from_slice(&1u16.to_le_bytes());
+        bytes[64..68].copy_from_slice(&3u32.to_le_bytes());
+        bytes[72..80].copy_from_slice(&120u64.to_le_bytes());
+        bytes[96..104].copy_from_slice(&(interpreter.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(interpreter.as_bytes());
+        bytes
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize_arch("amd64"), "x86_64");
+        assert_eq!(normalize_arch("arm64"), "aarch64");
+        assert_eq!(normalize_arch("s390x"), "s390x");
+        assert_eq!(normalize_libc("default"), Some("gnu".to_string()));
+        assert_eq!(normalize_libc("musl"), Some("musl".to_string()));
+        assert_eq!(normalize_libc("unknown"), None);
+    }
+
+    #[test]
+    fn test_mismatch() {
+        let host = platform("x86_64", "gnu");
+
+        assert!(platform("x86_64", "gnu").mismatch(&host).is_none());
+        assert!(platform("aarch64", "gnu").mismatch(&host).unwrap().contains("aarch64"));
+        assert!(platform("x86_64", "musl").mismatch(&host).unwrap().contains("musl"));
+        assert!(Platform::default().mismatch(&host).is_none());
+    }
+
+    #[test]
+    fn test_elf_platform() {
+        assert_eq!(elf_platform(&fake_elf(62, "/lib64/ld-linux-x86-64.so.2\0")), platform("x86_64", "gnu"));
+        assert_eq!(elf_platform(&fake_elf(183, "/lib/ld-musl-aarch64.so.1\0")), platform("aarch64", "musl"));
+        assert_eq!(elf_platform(b"#!/bin/sh\nexec java"), Platform::default());
+    }
+
+    #[test]
+    fn test_elf_platform_damaged() {
+        let mut huge_offset = fake_elf(62, "/lib64/ld-linux-x86-64.so.2\0");
+        huge_offset[32..40].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(elf_platform(&huge_offset), platform_arch("x86_64"));
+
+        let mut huge_entry = fake_elf(62, "/lib64/ld-linux-x86-64.so.2\0");
+        huge_entry[54..56].copy_from_slice(&u16::MAX.to_le_bytes());
+        huge_entry[56..58].copy_from_slice(&u16::MAX.to_le_bytes());
+        assert_eq!(elf_platform(&huge_entry), platform("x86_64", "gnu"));
+
+        let mut huge_interpreter = fake_elf(62, "/lib64/ld-linux-x86-64.so.2\0");
+        huge_interpreter[72..80].copy_from_slice(&u64::MAX.to_le_bytes());
+        huge_interpreter[96..104].copy_from_slice(&2u64.to_le_bytes());
+        assert_eq!(elf_platform(&huge_interpreter), platform_arch("x86_64"));
+
+        let truncated = fake_elf(62, "/lib64/ld-linux-x86-64.so.2\0");
+        assert_eq!(elf_platform(&truncated[..100]), platform_arch("x86_64"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_host_libc() {
+        // Whatever this machine is, it has one and knowing it never depends on stray files.
+        assert!(host_libc().is_some());
+    }
+}
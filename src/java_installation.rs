@@ -0,0 +1,230 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use log::info;
+use crate::launch_options::{JavaVersion, JSA_DIR};
+
+/// What we know about a Java install, mostly from its `release` file.
+///
+/// A missing release file (some JRE 8 layouts, stripped jlink images) just leaves the
+/// release values empty so callers can fall back to other ways of finding them.
+#[derive(Debug, Clone, Default)]
+pub struct JavaInstallation {
+    pub home: PathBuf,
+    pub has_release_file: bool,
+    pub java_version: Option<JavaVersion>,
+    pub java_runtime_version: Option<String>,
+    pub implementor: Option<String>,
+    pub os_arch: Option<String>,
+    pub os_name: Option<String>,
+    pub libc: Option<String>,
+    pub modules: Vec<String>,
+    /// Why this Java cannot run on this machine, if it cannot.
+    pub incompatibility: Option<String>,
+    has_modules_image: bool,
+    has_default_cds_archive: bool,
+}
+
+impl JavaInstallation {
+    pub fn from_home(home: &Path) -> JavaInstallation {
+        let release = fs::read_to_string(home.join("release")).ok();
+        let mut installation = JavaInstallation::parse_release(home, release.as_deref().unwrap_or(""));
+
+        installation.has_release_file = release.is_some();
+        installation.has_modules_image = home.join("lib").join("modules").exists();
+        installation.has_default_cds_archive = JavaInstallation::find_default_cds_archive(home);
+        installation
+    }
+
+    fn parse_release(home: &Path, contents: &str) -> JavaInstallation {
+        let mut installation = JavaInstallation { home: home.to_path_buf(), ..Default::default() };
+
+        for line in contents.lines() {
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim().trim_matches('"').trim()),
+                None => continue,
+            };
+
+            if value.is_empty() {
+                continue;
+            }
+
+            match key {
+                "JAVA_VERSION" => installation.java_version = JavaVersion::parse(value),
+                "JAVA_RUNTIME_VERSION" => installation.java_runtime_version = Some(value.to_string()),
+                "IMPLEMENTOR" => installation.implementor = Some(value.to_string()),
+                "OS_ARCH" => installation.os_arch = Some(value.to_string()),
+                "OS_NAME" => installation.os_name = Some(value.to_string()),
+                "LIBC" => installation.libc = Some(value.to_string()),
+                "MODULES" => installation.modules = value.split_whitespace().map(|m| m.to_string()).collect(),
+                _ => {}
+            }
+        }
+
+        installation
+    }
+
+    /// Serialize for the probe cache.  Same KEY="value" shape as a release file plus the
+    /// facts we otherwise get by looking at the filesystem.
+    pub(crate) fn to_cache(&self) -> String {
+        let mut lines = vec![
+            format!("HAS_RELEASE_FILE=\"{}\"", self.has_release_file),
+            format!("HAS_MODULES_IMAGE=\"{}\"", self.has_modules_image),
+            format!("HAS_DEFAULT_CDS_ARCHIVE=\"{}\"", self.has_default_cds_archive),
+            format!("MODULES=\"{}\"", self.modules.join(" ")),
+        ];
+        let values = [
+            ("JAVA_VERSION", self.java_version.as_ref().map(|v| v.to_string())),
+            ("JAVA_RUNTIME_VERSION", self.java_runtime_version.clone()),
+            ("IMPLEMENTOR", self.implementor.clone()),
+            ("OS_ARCH", self.os_arch.clone()),
+            ("OS_NAME", self.os_name.clone()),
+            ("LIBC", self.libc.clone()),
+            ("INCOMPATIBILITY", self.incompatibility.clone()),
+        ];
+
+        for (key, value) in values.iter() {
+            if let Some(value) = value {
+                lines.push(format!("{}=\"{}\"", key, value));
+            }
+        }
+
+        lines.join("\n")
+    }
+
+    pub(crate) fn from_cache(home: &Path, contents: &str) -> JavaInstallation {
+        let mut installation = JavaInstallation::parse_release(home, contents);
+
+        for line in contents.lines() {
+            match line.split_once('=') {
+                Some(("HAS_RELEASE_FILE", value)) => installation.has_release_file = value == "\"true\"",
+                Some(("HAS_MODULES_IMAGE", value)) => installation.has_modules_image = value == "\"true\"",
+                Some(("HAS_DEFAULT_CDS_ARCHIVE", value)) => installation.has_default_cds_archive = value == "\"true\"",
+                Some(("INCOMPATIBILITY", value)) => installation.incompatibility = Some(value.trim_matches('"').to_string()),
+                _ => {}
+            }
+        }
+
+        installation
+    }
+
+    // The JDK ships a default CDS archive (classes.jsa) in its server VM directory.
+    fn find_default_cds_archive(home: &Path) -> bool {
+        match fs::read_dir(home.join(JSA_DIR).join("server")) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .any(|entry| entry.path().to_string_lossy().ends_with(".jsa")),
+            Err(_) => false,
+        }
+    }
+
+    pub fn is_modular(&self) -> bool {
+        !self.modules.is_empty() || self.has_modules_image
+    }
+
+    pub fn has_module(&self, module: &str) -> bool {
+        self.modules.iter().any(|m| m == module)
+    }
+
+    /// Any .jsa in the server VM directory means the VM can use CDS archives.
+    pub fn has_appcds(&self) -> bool {
+        self.has_default_cds_archive
+    }
+
+    /// Only pre-module (<= 8) JREs have rt.jar.
+    pub fn has_legacy_layout(&self) -> bool {
+        self.home.join("lib").join("rt.jar").exists() && !self.has_modules_image
+    }
+
+    pub fn log_diagnostics(&self) {
+        info!("Java installation: {:?}", self.home);
+        info!("  release file: {}", self.has_release_file);
+        info!("  JAVA_VERSION: {:?}", self.java_version.as_ref().map(|v| v.to_string()));
+        info!("  JAVA_RUNTIME_VERSION: {:?}", self.java_runtime_version);
+        info!("  IMPLEMENTOR: {:?}", self.implementor);
+        info!("  OS_NAME: {:?} OS_ARCH: {:?} LIBC: {:?}", self.os_name, self.os_arch, self.libc);
+        info!("  MODULES: {}", self.modules.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use crate::java_installation::JavaInstallation;
+    use crate::launch_options::JavaVersion;
+
+    const TEMURIN_RELEASE: &str = r#"IMPLEMENTOR="Eclipse Adoptium"
+IMPLEMENTOR_VERSION="Temurin-17.0.2+8"
+JAVA_VERSION="17.0.2"
+JAVA_VERSION_DATE="2022-01-18"
+JAVA_RUNTIME_VERSION="17.0.2+8"
+LIBC="gnu"
+MODULES="java.base java.compiler java.datatransfer java.management"
+OS_ARCH="x86_64"
+OS_NAME="Linux"
+SOURCE=".:git:1a4a4d1b0f3a"
+"#;
+
+    #[test]
+    fn test_parse_release() {
+        let java = JavaInstallation::parse_release(Path::new("/opt/jdk"), TEMURIN_RELEASE);
+
+        assert_eq!(java.java_version, JavaVersion::parse("17.0.2"));
+        assert_eq!(java.java_runtime_version.as_deref(), Some("17.0.2+8"));
+        assert_eq!(java.implementor.as_deref(), Some("Eclipse Adoptium"));
+        assert_eq!(java.os_arch.as_deref(), Some("x86_64"));
+        assert_eq!(java.os_name.as_deref(), Some("Linux"));
+        assert_eq!(java.libc.as_deref(), Some("gnu"));
+        assert_eq!(java.modules.len(), 4);
+        assert!(java.has_module("java.management"));
+        assert!(java.is_modular());
+    }
+
+    #[test]
+    fn test_parse_release_java8() {
+        let java = JavaInstallation::parse_release(Path::new("/opt/jdk8"), "JAVA_VERSION=\"1.8.0_292\"\nOS_NAME=\"Linux\"\n");
+
+        assert_eq!(java.java_version.as_ref().unwrap().major(), 8);
+        assert!(java.modules.is_empty());
+        assert!(!java.is_modular());
+        assert!(!java.has_appcds());
+    }
+
+    #[test]
+    fn test_has_appcds_java11() {
+        let mut java = JavaInstallation::parse_release(Path::new("/opt/jdk11"), "JAVA_VERSION=\"11.0.22\"\n");
+        assert!(!java.has_appcds());
+
+        java.has_default_cds_archive = true;
+        assert!(java.has_appcds());
+    }
+
+    #[test]
+    fn test_cache_round_trip() {
+        let mut java = JavaInstallation::parse_release(Path::new("/opt/jdk"), TEMURIN_RELEASE);
+        java.has_release_file = true;
+        java.has_default_cds_archive = true;
+
+        let cached = JavaInstallation::from_cache(Path::new("/opt/jdk"), &java.to_cache());
+
+        assert_eq!(cached.java_version, java.java_version);
+        assert_eq!(cached.libc, java.libc);
+        assert_eq!(cached.modules, java.modules);
+        assert!(cached.has_release_file);
+        assert!(cached.has_appcds());
+        assert!(!cached.has_modules_image);
+        assert!(cached.incompatibility.is_none());
+
+        java.incompatibility = Some("it needs musl libc but this machine uses gnu libc".to_string());
+        let cached = JavaInstallation::from_cache(Path::new("/opt/jdk"), &java.to_cache());
+        assert_eq!(cached.incompatibility, java.incompatibility);
+    }
+
+    #[test]
+    fn test_parse_release_empty() {
+        let java = JavaInstallation::parse_release(Path::new("/opt/jdk"), "");
+
+        assert!(java.java_version.is_none());
+        assert!(java.libc.is_none());
+        assert!(!java.is_modular());
+    }
+}
@@ -0,0 +1,135 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use log::info;
+
+pub const JAVA_VERSION_FILE: &str = ".java-version";
+pub const TOOL_VERSIONS_FILE: &str = ".tool-versions";
+
+/// A Java version pinned by a project through jenv's .java-version or asdf's .tool-versions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PinnedVersion {
+    pub file: PathBuf,
+    pub version: String,
+}
+
+/// Walk up from the start directory and return the nearest pinned Java version.
+///
+/// Within one directory .java-version is checked before .tool-versions.  A .tool-versions
+/// without a java entry does not stop the search (asdf looks further up for that tool too).
+pub(crate) fn find_pinned_version(start: &Path) -> Option<PinnedVersion> {
+    for dir in start.ancestors() {
+        let java_version = dir.join(JAVA_VERSION_FILE);
+        if let Some(version) = fs::read_to_string(&java_version).ok().and_then(|c| parse_java_version_file(&c)) {
+            info!("Found pinned Java version {} in {:?}", version, &java_version);
+            return Some(PinnedVersion { file: java_version, version });
+        }
+
+        let tool_versions = dir.join(TOOL_VERSIONS_FILE);
+        if let Some(version) = fs::read_to_string(&tool_versions).ok().and_then(|c| parse_tool_versions_file(&c)) {
+            info!("Found pinned Java version {} in {:?}", version, &tool_versions);
+            return Some(PinnedVersion { file: tool_versions, version });
+        }
+    }
+
+    None
+}
+
+/// .java-version holds a single version or jenv name (e.g. `17`, `temurin-17.0.2`, `openjdk64-11.0.2`).
+fn parse_java_version_file(contents: &str) -> Option<String> {
+    contents
+        .lines()
+        .map(|line| line.trim())
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .and_then(version_from_name)
+}
+
+/// .tool-versions lines are `<tool> <version> [<fallback version>...]`.
+fn parse_tool_versions_file(contents: &str) -> Option<String> {
+    contents
+        .lines()
+        .map(|line| line.split('#').next().unwrap().trim())
+        .find_map(|line| {
+            let mut fields = line.split_whitespace();
+
+            if fields.next() == Some("java") {
+                fields.next().and_then(version_from_name)
+            } else {
+                None
+            }
+        })
+}
+
+/// Strip a vendor prefix (`temurin-`, `openjdk64-`) and reduce to at most feature.interim.update
+/// so vendor-specific trailing numbers (`corretto-21.0.1.12.1`) do not make the pin unmatchable.
+/// Special values like `system` or asdf's `ref:`/`path:` are not pins.
+fn version_from_name(name: &str) -> Option<String> {
+    let start = name
+        .char_indices()
+        .find(|(i, c)| c.is_ascii_digit() && (*i == 0 || name[..*i].ends_with('-')))
+        .map(|(i, _)| i)?;
+    let version = &name[start..];
+    let end = version.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(version.len());
+    let numbers: Vec<&str> = version[..end].split('.').filter(|n| !n.is_empty()).collect();
+    let limit = if numbers.first() == Some(&"1") { 4 } else { 3 };
+
+    if numbers.is_empty() {
+        return None;
+    }
+
+    Some(numbers.into_iter().take(limit).collect::<Vec<&str>>().join("."))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::file_helper::tests::temp_dir;
+    use crate::java_version_file::{find_pinned_version, parse_java_version_file, parse_tool_versions_file, version_from_name};
+
+    #[test]
+    fn test_version_from_name() {
+        assert_eq!(version_from_name("17"), Some("17".to_string()));
+        assert_eq!(version_from_name("17.0.2"), Some("17.0.2".to_string()));
+        assert_eq!(version_from_name("temurin-17.0.2+8"), Some("17.0.2".to_string()));
+        assert_eq!(version_from_name("openjdk64-11.0.2"), Some("11.0.2".to_string()));
+        assert_eq!(version_from_name("corretto-21.0.1.12.1"), Some("21.0.1".to_string()));
+        assert_eq!(version_from_name("1.8"), Some("1.8".to_string()));
+        assert_eq!(version_from_name("openjdk64-1.8.0.292"), Some("1.8.0.292".to_string()));
+        assert_eq!(version_from_name("system"), None);
+        assert_eq!(version_from_name("path:/opt/jdk"), None);
+    }
+
+    #[test]
+    fn test_parse_java_version_file() {
+        assert_eq!(parse_java_version_file("21\n"), Some("21".to_string()));
+        assert_eq!(parse_java_version_file("\n# comment\ntemurin-17.0.2\n"), Some("17.0.2".to_string()));
+        assert_eq!(parse_java_version_file("system\n"), None);
+        assert_eq!(parse_java_version_file(""), None);
+    }
+
+    #[test]
+    fn test_parse_tool_versions_file() {
+        assert_eq!(parse_tool_versions_file("ruby 3.1.4\njava temurin-21.0.2+13.0.LTS\n"), Some("21.0.2".to_string()));
+        assert_eq!(parse_tool_versions_file("java openjdk-17 openjdk-11 # fallback\n"), Some("17".to_string()));
+        assert_eq!(parse_tool_versions_file("ruby 3.1.4\nnodejs 20.1.0\n"), None);
+        assert_eq!(parse_tool_versions_file("javascript 1.0\n"), None);
+    }
+
+    #[test]
+    fn test_find_pinned_version_walks_up() {
+        let root = temp_dir("pinned_version");
+        let nested = root.join("app").join("lib");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(root.join(".java-version"), "17\n").unwrap();
+        fs::write(root.join("app").join(".tool-versions"), "ruby 3.1.4\n").unwrap();
+
+        let pinned = find_pinned_version(&nested);
+
+        fs::write(root.join("app").join(".tool-versions"), "java temurin-21.0.2\n").unwrap();
+        let nearer = find_pinned_version(&nested);
+
+        let pinned = pinned.unwrap();
+        assert_eq!(pinned.version, "17");
+        assert_eq!(pinned.file, root.join(".java-version"));
+        assert_eq!(nearer.unwrap().version, "21.0.2");
+    }
+}
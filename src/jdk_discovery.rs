@@ -0,0 +1,182 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use log::info;
+use crate::environment::Environment;
+use crate::java_compatibility::incompatibility;
+use crate::java_installation::JavaInstallation;
+use crate::launch_options::{JavaVersion, JAVA_NAME};
+use crate::probe_cache::ProbeCache;
+
+/// A JDK found in one of the well-known install roots.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiscoveredJdk {
+    pub home: PathBuf,
+    pub version: JavaVersion,
+}
+
+/// Directories which commonly contain one JDK per subdirectory.  Order matters
+/// as it is the tie-breaker when two JDKs report the same version.
+pub(crate) fn search_roots(env: &Environment) -> Vec<PathBuf> {
+    let mut roots = vec![PathBuf::from("/usr/lib/jvm"), PathBuf::from("/opt/java")];
+
+    if let Some(home) = &env.home {
+        let home = PathBuf::from(home);
+
+        roots.push(home.join(".sdkman").join("candidates").join("java"));
+        roots.push(home.join(".asdf").join("installs").join("java"));
+        roots.push(home.join(".jdks"));
+        roots.push(home.join(".jenv").join("versions"));
+    }
+
+    roots
+}
+
+/// Every valid JDK in the search roots, best candidate first.  Checking a JDK means reading
+/// its release file and bin/java so the result is kept in the probe cache.
+pub(crate) fn discover(env: &Environment) -> Vec<DiscoveredJdk> {
+    let roots = search_roots(env);
+    let cache = ProbeCache::new(env);
+
+    if let Some(found) = cache.as_ref().and_then(|cache| cache.load_discovery(&roots)) {
+        return found;
+    }
+
+    let found = scan(&roots);
+    if let Some(cache) = &cache {
+        cache.store_discovery(&roots, &found);
+    }
+    found
+}
+
+/// Selection policy: the newest Java version wins but any release beats an early access
+/// build.  When two installs report the same version the one from the earlier search root
+/// wins.  Symlinked entries (sdkman `current`, jenv versions) are only counted once.
+fn scan(roots: &[PathBuf]) -> Vec<DiscoveredJdk> {
+    info!("discovering installed JDKs");
+    let mut found: Vec<DiscoveredJdk> = vec![];
+    let mut seen: Vec<PathBuf> = vec![];
+
+    for root in roots {
+        for candidate in candidates_in(root) {
+            let real = fs::canonicalize(&candidate).unwrap_or_else(|_| candidate.clone());
+            if seen.contains(&real) {
+                continue;
+            }
+            seen.push(real);
+
+            if let Some(jdk) = validate(&candidate) {
+                info!("Discovered JDK {:?} version {}", &jdk.home, &jdk.version);
+                found.push(jdk);
+            }
+        }
+    }
+
+    // stable sort keeps search root order for equal versions.
+    found.sort_by(|a, b| {
+        a.version.is_pre_release().cmp(&b.version.is_pre_release()).then_with(|| b.version.cmp(&a.version))
+    });
+    found
+}
+
+pub(crate) fn candidates_in(root: &Path) -> Vec<PathBuf> {
+    let entries = match fs::read_dir(root) {
+        Ok(entries) => entries,
+        Err(_) => return vec![],
+    };
+
+    let mut candidates: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect();
+
+    // read_dir order is filesystem dependent so keep it predictable.
+    candidates.sort();
+    candidates
+}
+
+/// A directory is a JDK if it has a release file with a JAVA_VERSION and a java executable
+/// which can run on this machine.
+fn validate(home: &Path) -> Option<DiscoveredJdk> {
+    if !home.join("bin").join(JAVA_NAME).exists() {
+        info!("Skipping {:?}: no {}", home, JAVA_NAME);
+        return None;
+    }
+
+    let installation = JavaInstallation::from_home(home);
+
+    if let Some(reason) = incompatibility(&installation, &home.join("bin").join(JAVA_NAME)) {
+        info!("Skipping {:?}: {}", home, reason);
+        return None;
+    }
+
+    match installation.java_version {
+        Some(version) => Some(DiscoveredJdk { home: home.to_path_buf(), version }),
+        None => {
+            info!("Skipping {:?}: no usable release file", home);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use crate::file_helper::tests::temp_dir;
+    use crate::jdk_discovery::{discover, search_roots};
+    use crate::environment::tests::empty_env;
+    use crate::launch_options::JAVA_NAME;
+
+    fn fake_jdk(root: &Path, name: &str, version: Option<&str>) {
+        let home = root.join(name);
+        fs::create_dir_all(home.join("bin")).unwrap();
+        fs::write(home.join("bin").join(JAVA_NAME), "").unwrap();
+        if let Some(version) = version {
+            fs::write(home.join("release"), format!("JAVA_VERSION=\"{}\"\n", version)).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_search_roots_include_home() {
+        let mut env = empty_env();
+
+        assert_eq!(search_roots(&env).len(), 2);
+
+        env.home = Some(PathBuf::from("/home/user").into_os_string());
+        let roots = search_roots(&env);
+
+        assert!(roots.contains(&PathBuf::from("/home/user/.sdkman/candidates/java")));
+        assert!(roots.contains(&PathBuf::from("/home/user/.jenv/versions")));
+    }
+
+    #[test]
+    fn test_discover_prefers_newest() {
+        let home = temp_dir("jdk_discovery");
+        let sdkman = home.join(".sdkman").join("candidates").join("java");
+        let jdks = home.join(".jdks");
+        fake_jdk(&sdkman, "11.0.14-tem", Some("11.0.14"));
+        fake_jdk(&sdkman, "17.0.2-ea", Some("17.0.2-ea"));
+        fake_jdk(&sdkman, "24-ea", Some("24-ea"));
+        fake_jdk(&jdks, "openjdk-17.0.2", Some("17.0.2"));
+        fake_jdk(&jdks, "broken", None);
+
+        let mut env = empty_env();
+        env.home = Some(home.as_os_str().to_os_string());
+
+        let versions = |env| -> Vec<String> {
+            discover(env).into_iter()
+                .filter(|jdk| jdk.home.starts_with(&home))
+                .map(|jdk| jdk.version.to_string())
+                .collect()
+        };
+        let found = versions(&env);
+        let cached = versions(&env);
+        fake_jdk(&jdks, "openjdk-21", Some("21.0.2"));
+        let installed = versions(&env);
+
+        assert_eq!(found, vec!["17.0.2".to_string(), "11.0.14".to_string(), "24-ea".to_string(), "17.0.2-ea".to_string()]);
+        assert_eq!(cached, found);
+        assert_eq!(installed[0], "21.0.2");
+    }
+}
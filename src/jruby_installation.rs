@@ -0,0 +1,312 @@
+use std::fs;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use log::info;
+use zip::ZipArchive;
+use crate::launch_options::JRUBY_JARS;
+
+const MANIFEST: &str = "META-INF/MANIFEST.MF";
+const JRUBY_PROPERTIES: &str = "org/jruby/jruby.properties";
+
+/// What a jruby jar tells us about itself.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct JRubyInfo {
+    pub version: Option<String>,
+    pub minimum_java: Option<u32>,
+}
+
+impl JRubyInfo {
+    /// Read version and minimum Java from the jar's manifest and jruby.properties.
+    pub fn from_jar(jar: &Path) -> JRubyInfo {
+        let mut archive = match File::open(jar).map(ZipArchive::new) {
+            Ok(Ok(archive)) => archive,
+            _ => {
+                info!("Unable to open {:?} as a jar", jar);
+                return JRubyInfo::default();
+            }
+        };
+        let manifest = read_entry(&mut archive, MANIFEST).unwrap_or_default();
+        let properties = read_entry(&mut archive, JRUBY_PROPERTIES).unwrap_or_default();
+
+        JRubyInfo::from_contents(&manifest, &properties)
+    }
+
+    fn from_contents(manifest: &str, properties: &str) -> JRubyInfo {
+        let manifest = parse_manifest(manifest);
+        let properties = parse_properties(properties);
+        let lookup = |entries: &Vec<(String, String)>, key: &str| entries.iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.clone());
+
+        let version = lookup(&properties, "version.jruby")
+            .or_else(|| lookup(&manifest, "Implementation-Version"))
+            .or_else(|| lookup(&manifest, "Bundle-Version"));
+        let minimum_java = version.as_deref().and_then(minimum_java_for);
+
+        JRubyInfo { version, minimum_java }
+    }
+
+    /// Serialize for the probe cache in the same KEY="value" shape as a Java entry.
+    pub(crate) fn to_cache(&self) -> String {
+        let mut lines = vec![];
+
+        if let Some(version) = &self.version {
+            lines.push(format!("VERSION=\"{}\"", version));
+        }
+        if let Some(minimum_java) = self.minimum_java {
+            lines.push(format!("MINIMUM_JAVA=\"{}\"", minimum_java));
+        }
+
+        lines.join("\n")
+    }
+
+    pub(crate) fn from_cache(contents: &str) -> JRubyInfo {
+        let mut info = JRubyInfo::default();
+
+        for line in contents.lines() {
+            match line.split_once('=').map(|(key, value)| (key, value.trim_matches('"'))) {
+                Some(("VERSION", value)) => info.version = Some(value.to_string()),
+                Some(("MINIMUM_JAVA", value)) => info.minimum_java = value.parse().ok(),
+                _ => {}
+            }
+        }
+
+        info
+    }
+}
+
+fn read_entry(archive: &mut ZipArchive<File>, name: &str) -> Option<String> {
+    let mut entry = archive.by_name(name).ok()?;
+    let mut contents = String::new();
+
+    entry.read_to_string(&mut contents).ok()?;
+    Some(contents)
+}
+
+/// Manifest lines are `Name: value` and a line starting with a space continues the previous value.
+fn parse_manifest(contents: &str) -> Vec<(String, String)> {
+    let mut entries: Vec<(String, String)> = vec![];
+
+    for line in contents.lines() {
+        if let Some(continuation) = line.strip_prefix(' ') {
+            if let Some((_, value)) = entries.last_mut() {
+                value.push_str(continuation);
+            }
+        } else if let Some((key, value)) = line.split_once(':') {
+            entries.push((key.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    entries
+}
+
+fn parse_properties(contents: &str) -> Vec<(String, String)> {
+    contents
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.starts_with('#') && !line.starts_with('!'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect()
+}
+
+/// Oldest Java each JRuby line supports according to its release notes: 9.0.0.0 needs
+/// Java 7, 9.2.0.0 Java 8 and 10.0.0.0 Java 21.  None for anything older or unparseable.
+fn minimum_java_for(jruby_version: &str) -> Option<u32> {
+    let mut parts = jruby_version.split('.').map(|part| part.parse::<u32>().ok());
+    let major = parts.next().flatten()?;
+    let minor = parts.next().flatten().unwrap_or(0);
+
+    match (major, minor) {
+        (major, _) if major >= 10 => Some(21),
+        (9, minor) if minor >= 2 => Some(8),
+        (9, _) => Some(7),
+        _ => None,
+    }
+}
+
+/// Names JRuby uses for its lib/jni subdirectories on this platform.  Most are
+/// `<arch>-<OS>` but macOS ships a single universal `Darwin` directory.
+pub(crate) fn jni_platform_dirs() -> Vec<String> {
+    let os = match std::env::consts::OS {
+        "linux" => "Linux",
+        "macos" => "Darwin",
+        "windows" => "Windows",
+        "freebsd" => "FreeBSD",
+        "openbsd" => "OpenBSD",
+        "dragonfly" => "DragonFlyBSD",
+        "solaris" | "illumos" => "SunOS",
+        "aix" => "AIX",
+        other => other,
+    };
+    let arch = match std::env::consts::ARCH {
+        "x86" => "i386",
+        "powerpc" => "ppc",
+        "powerpc64" => "ppc64",
+        "powerpc64le" => "ppc64le",
+        "sparc64" => "sparcv9",
+        other => other,
+    };
+
+    vec![format!("{}-{}", arch, os), os.to_string()]
+}
+
+/// Check a JRuby install has what we need before we hand it to the JVM.  Every
+/// problem found is returned so a broken install can be fixed in one go.
+pub(crate) fn validate(jruby_home: &Path, java_is_modular: bool) -> Vec<String> {
+    info!("Validating JRuby installation at {:?}", jruby_home);
+    let mut problems = vec![];
+    let lib = jruby_home.join("lib");
+
+    if !lib.is_dir() {
+        problems.push(format!("{} is missing (is JRUBY_HOME pointing at a JRuby install?)", lib.display()));
+        return problems;
+    }
+
+    let jar = JRUBY_JARS.iter().map(|jar| lib.join(jar)).find(|jar| jar.exists());
+    match &jar {
+        None => problems.push(format!("neither {} nor {} exists", lib.join(JRUBY_JARS[0]).display(), lib.join(JRUBY_JARS[1]).display())),
+        // jruby-complete.jar carries its own native stubs.
+        Some(jar) if jar.ends_with(JRUBY_JARS[0]) => {
+            let jni = lib.join("jni");
+            let platforms = jni_platform_dirs();
+
+            if !platforms.iter().any(|platform| has_files(&jni.join(platform))) {
+                problems.push(format!("no native stubs for this platform in {} (expected {})",
+                                      jni.display(), platforms.join(" or ")));
+            }
+        }
+        Some(_) => {}
+    }
+
+    // Without it we fall back to known --add-opens so it only matters if it is there but unusable.
+    if java_is_modular {
+        let module_opts = jruby_home.join("bin").join(".jruby.module_opts");
+        if module_opts.exists() && File::open(&module_opts).is_err() {
+            problems.push(format!("{} is not readable", module_opts.display()));
+        }
+    }
+
+    for jar in jars_in(&lib) {
+        if let Err(e) = File::open(&jar) {
+            problems.push(format!("{} is not readable: {}", jar.display(), e));
+        }
+    }
+
+    problems
+}
+
+fn has_files(dir: &Path) -> bool {
+    fs::read_dir(dir)
+        .map(|mut entries| entries.any(|entry| entry.is_ok()))
+        .unwrap_or(false)
+}
+
+fn jars_in(dir: &Path) -> Vec<PathBuf> {
+    match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.to_string_lossy().ends_with(".jar"))
+            .collect(),
+        Err(_) => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::fs::File;
+    use std::io::Write;
+    use zip::write::FileOptions;
+    use zip::{CompressionMethod, ZipWriter};
+    use crate::file_helper::tests::temp_dir;
+    use crate::jruby_installation::{jni_platform_dirs, minimum_java_for, validate, JRubyInfo};
+
+    #[test]
+    fn test_jruby_info_from_contents() {
+        let manifest = "Manifest-Version: 1.0\r\nImplementation-Version: 9.4.8\r\n .0\r\n";
+        assert_eq!(JRubyInfo::from_contents(manifest, ""),
+                   JRubyInfo { version: Some("9.4.8.0".to_string()), minimum_java: Some(8) });
+
+        let properties = "# comment\nversion.ruby=3.4.2\nversion.jruby=10.0.0.0\n";
+        assert_eq!(JRubyInfo::from_contents("Manifest-Version: 1.0\n", properties),
+                   JRubyInfo { version: Some("10.0.0.0".to_string()), minimum_java: Some(21) });
+
+        assert_eq!(JRubyInfo::from_contents("", ""), JRubyInfo::default());
+    }
+
+    #[test]
+    fn test_minimum_java_for() {
+        assert_eq!(minimum_java_for("10.0.2.0"), Some(21));
+        assert_eq!(minimum_java_for("9.4.8.0"), Some(8));
+        assert_eq!(minimum_java_for("9.2.0.0"), Some(8));
+        assert_eq!(minimum_java_for("9.1.17.0"), Some(7));
+        assert_eq!(minimum_java_for("1.7.27"), None);
+        assert_eq!(minimum_java_for("unknown"), None);
+    }
+
+    #[test]
+    fn test_jruby_info_cache_round_trip() {
+        let info = JRubyInfo { version: Some("9.4.8.0".to_string()), minimum_java: Some(8) };
+
+        assert_eq!(JRubyInfo::from_cache(&info.to_cache()), info);
+        assert_eq!(JRubyInfo::from_cache(&JRubyInfo::default().to_cache()), JRubyInfo::default());
+    }
+
+    #[test]
+    fn test_jruby_info_from_jar() {
+        let dir = temp_dir("jruby_info");
+        let jar = dir.join("jruby.jar");
+        let mut writer = ZipWriter::new(File::create(&jar).unwrap());
+        let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+        writer.start_file("META-INF/MANIFEST.MF", options).unwrap();
+        writer.write_all(b"Manifest-Version: 1.0\nImplementation-Version: 9.4.8.0\n").unwrap();
+        writer.start_file("org/jruby/jruby.properties", options).unwrap();
+        writer.write_all(b"version.jruby=9.4.9.0\n").unwrap();
+        writer.finish().unwrap();
+
+        let info = JRubyInfo::from_jar(&jar);
+        let missing = JRubyInfo::from_jar(&dir.join("nope.jar"));
+
+        assert_eq!(info.version.as_deref(), Some("9.4.9.0"));
+        assert_eq!(info.minimum_java, Some(8));
+        assert_eq!(missing, JRubyInfo::default());
+    }
+
+    #[test]
+    fn test_validate_installation() {
+        let home = temp_dir("jruby_installation");
+        let lib = home.join("lib");
+
+        let missing_lib = validate(&home, false);
+
+        fs::create_dir_all(&lib).unwrap();
+        let missing_jar = validate(&home, false);
+
+        fs::write(lib.join("jruby.jar"), "").unwrap();
+        let missing_jni = validate(&home, false);
+
+        let platform = lib.join("jni").join(&jni_platform_dirs()[0]);
+        fs::create_dir_all(&platform).unwrap();
+        fs::write(platform.join("libjffi-1.2.so"), "").unwrap();
+        let valid = validate(&home, true);
+
+        assert!(missing_lib[0].contains("is missing"));
+        assert!(missing_jar[0].contains("jruby-complete.jar"));
+        assert!(missing_jni[0].contains("native stubs"));
+        assert!(valid.is_empty(), "{:?}", valid);
+    }
+
+    #[test]
+    fn test_jruby_complete_needs_no_jni() {
+        let home = temp_dir("jruby_complete");
+        fs::create_dir_all(home.join("lib")).unwrap();
+        fs::write(home.join("lib").join("jruby-complete.jar"), "").unwrap();
+
+        let problems = validate(&home, false);
+
+        assert!(problems.is_empty(), "{:?}", problems);
+    }
+}
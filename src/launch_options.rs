@@ -1,19 +1,39 @@
 use core::fmt;
 use log::{error, info, warn};
+use std::cmp::Ordering;
+use std::collections::VecDeque;
 use std::error::Error;
 use std::fmt::Formatter;
 use std::path::{Path, PathBuf};
 use std::{env, fs};
 use std::ffi::OsString;
-use std::process::exit;
-use regex::Regex;
+use std::process::Command;
+use crate::argfile;
 use crate::environment::Environment;
-use crate::file_helper::find_from_path;
+use crate::file_helper::{find_from_path, resolve_symlinks};
 use crate::file_logger;
+use crate::java_compatibility::incompatibility;
+use crate::java_installation::JavaInstallation;
+use crate::java_version_file::find_pinned_version;
+use crate::jdk_discovery;
+use crate::jruby_installation;
+use crate::jruby_installation::JRubyInfo;
+use crate::launcher_config::LauncherConfig;
+use crate::jdk_discovery::DiscoveredJdk;
+use crate::module_opts;
+use crate::module_opts::{DEFAULT_MODULE_OPTIONS, MODULE_OPTS_FILE};
+use crate::option_fragments;
+use crate::option_table;
+use crate::option_table::Arity;
 use crate::os_string_ext::OsStringExt;
+use crate::probe_cache::ProbeCache;
+use crate::version_constraint::VersionConstraint;
 
 pub const MAIN_CLASS: &str = "org/jruby/Main";
 
+// A JRuby home has one of these in lib (in order of preference).
+pub const JRUBY_JARS: [&str; 2] = ["jruby.jar", "jruby-complete.jar"];
+
 pub const XSS_DEFAULT: &str = "2048k";
 pub const XSS_DEFAULT_OPT: &str = "-Xss2048k";
 
@@ -24,6 +44,9 @@ pub const DEV_MODE_JAVA_OPTIONS: [&str; 4] = [
     "-Djruby.compile.invokedynamic=false"
 ];
 
+// Where a distribution may bundle its own Java runtime within JRuby home.
+pub const BUNDLED_RUNTIME_DIRS: [&str; 2] = ["jre", "runtime"];
+
 #[cfg(target_os = "windows")]
 pub const JAVA_NAME: &str = "java.exe";
 
@@ -45,9 +68,120 @@ pub const JAVA_NAME: &str = "java";
 #[cfg(not(windows))]
 pub const SHELL: &str = "-Djruby.shell=/bin/sh";
 
+/// A parsed Java version string.
+///
+/// Handles legacy versions (`1.8.0_292-b10` is feature release 8, update 292) and
+/// JEP 223 versions (`$VNUM(-$PRE)?(\+$BUILD)?(-$OPT)?` like `21.0.1+12-LTS` or `23-ea`).
+/// Ordering follows JEP 223: numeric components (trailing zeros ignored), then a
+/// pre-release sorts before its GA, then build number.
+#[derive(Debug, Clone, Default)]
+pub struct JavaVersion {
+    raw: String,
+    components: Vec<u32>,
+    pre: Option<String>,
+    build: Option<u32>,
+}
+
+impl JavaVersion {
+    pub fn parse(version: &str) -> Option<JavaVersion> {
+        let raw = version.trim();
+        let (rest, build) = match raw.split_once('+') {
+            Some((rest, build)) => {
+                let digits: String = build.chars().take_while(|c| c.is_ascii_digit()).collect();
+                (rest, digits.parse::<u32>().ok())
+            }
+            None => (raw, None),
+        };
+        let (vnum, pre) = match rest.split_once('-') {
+            Some((vnum, pre)) => (vnum, Some(pre.to_string())),
+            None => (rest, None),
+        };
+
+        let (components, build, pre) = if let Some(legacy) = vnum.strip_prefix("1.") {
+            // 1.8.0_292(-b10)? where -bNN is the build and not a pre-release.
+            let (legacy, update) = match legacy.split_once('_') {
+                Some((legacy, update)) => (legacy, Some(update.parse::<u32>().ok()?)),
+                None => (legacy, None),
+            };
+            let mut components = JavaVersion::numbers(legacy)?;
+            components.truncate(2);
+            components.extend(update);
+            match pre.as_deref().and_then(|pre| pre.strip_prefix('b')).and_then(|b| b.parse::<u32>().ok()) {
+                Some(legacy_build) => (components, Some(legacy_build), None),
+                None => (components, build, pre),
+            }
+        } else {
+            (JavaVersion::numbers(vnum)?, build, pre)
+        };
+
+        Some(JavaVersion { raw: raw.to_string(), components, pre, build })
+    }
+
+    fn numbers(vnum: &str) -> Option<Vec<u32>> {
+        let numbers: Result<Vec<u32>, _> = vnum.split('.').map(|n| n.parse::<u32>()).collect();
+
+        numbers.ok().filter(|numbers| !numbers.is_empty())
+    }
+
+    /// Feature release number (8 for 1.8.0_292, 21 for 21.0.1).
+    pub fn major(&self) -> u32 {
+        self.components.first().copied().unwrap_or(0)
+    }
+
+    pub fn components(&self) -> &[u32] {
+        &self.components
+    }
+
+    pub fn is_pre_release(&self) -> bool {
+        self.pre.is_some()
+    }
+
+    fn significant_components(&self) -> &[u32] {
+        let length = self.components.iter().rposition(|n| *n != 0).map_or(0, |i| i + 1);
+
+        &self.components[0..length]
+    }
+}
+
+// What we report before a Java has been selected.
+static UNKNOWN_JAVA_VERSION: JavaVersion = JavaVersion { raw: String::new(), components: Vec::new(), pre: None, build: None };
+
+impl fmt::Display for JavaVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
+
+impl Ord for JavaVersion {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.significant_components().cmp(other.significant_components())
+            .then_with(|| match (&self.pre, &other.pre) {
+                (None, None) => Ordering::Equal,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(_), None) => Ordering::Less,
+                (Some(one), Some(two)) => one.cmp(two),
+            })
+            .then_with(|| self.build.cmp(&other.build))
+    }
+}
+
+impl PartialOrd for JavaVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for JavaVersion {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for JavaVersion {}
+
 #[derive(Debug, Clone)]
 pub struct LaunchError {
-    pub message: &'static str,
+    pub message: String,
 }
 
 impl fmt::Display for LaunchError {
@@ -66,97 +200,161 @@ pub fn new(args: Vec<OsString>) -> Result<LaunchOptions, Box<dyn Error>> {
     let mut options = LaunchOptions::default();
     let env = Environment::from_env(args);
 
+    // Start tracing before anything else so finding JRuby home and reading config show up.
+    options.launcher_logfile = trace_argument(&env.args);
+    let mut tracing = options.launcher_logfile.is_some();
+    if tracing {
+        options.setup_logging();
+    }
+
+    // Config comes first so environment variables and argv can override it.  A missing
+    // JRuby home or a broken config is only an error once we know -Xhelp or -Xversion
+    // (which should still work on a broken install) were not asked for.
+    let jruby_home = env.determine_jruby_home(|f| f.exists());
+    let config_error = match LauncherConfig::load(&env, jruby_home.as_ref().ok().map(|home| home.as_path())) {
+        Ok(config) => {
+            options.config = config;
+            None
+        }
+        Err(e) => Some(e),
+    };
+    options.apply_config();
+
     options.parse(&env)?;
 
-    if options.launcher_logfile.is_some() {
+    if !tracing && options.launcher_logfile.is_some() {
         options.setup_logging();
-    };
+        tracing = true;
+    }
+    if tracing {
+        info!("Launcher config files: {:?}", options.config.files);
+    }
 
-    let executable = env.determine_jruby_executable(|f| f.exists())?;
-    options.jruby_home = Some(executable.ancestors().take(3).collect());
+    if let Some(e) = config_error {
+        if !options.print_help && !options.print_version {
+            return Err(e);
+        }
+        eprintln!("Warning: ignoring launcher config: {}", e);
+    }
+
+    if options.print_completion.is_some() || options.print_profiles {
+        return Ok(options);
+    }
+
+    // Support asks for -Xversion from broken installs so it reports whatever it could find.
+    if options.print_version {
+        options.jruby_home = jruby_home.ok();
+        if let Err(e) = options.determine_java_location(&env) {
+            info!("No usable Java for -Xversion: {}", e);
+        }
+        if options.jruby_home.is_some() {
+            options.read_jruby_info(&env);
+        }
+        return Ok(options);
+    }
+
+    options.jruby_home = Some(jruby_home?);
     info!("launch_options = {:?}", options);
     options.determine_java_location(&env)?;
     info!("launch_options = {:?}", options);
+
+    options.validate_installation()?;
+    options.check_jruby_requirements(&env)?;
     options.prepare_options(&env)?;
     info!("launch_options = {:?}", options);
 
     Ok(options)
 }
 
+// -Xtrace as given on the command line (before any other processing).
+fn trace_argument(args: &[OsString]) -> Option<PathBuf> {
+    args.iter()
+        .take_while(|arg| *arg != "--")
+        .position(|arg| arg == "-Xtrace")
+        .and_then(|i| args.get(i + 1))
+        .map(PathBuf::from)
+}
+
+// Where an argument came from, which decides what in it may still be expanded.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Origin {
+    CommandLine,
+    JRubyOpts,
+    ArgFile,
+    Alias,
+}
+
 #[derive(Debug, Default)]
 pub struct LaunchOptions {
-    fork_java: bool,
+    pub(crate) fork_java: bool,
     pub(crate) command_only: bool,
-    no_boot_classpath: bool,
+    pub(crate) print_version: bool,
+    pub(crate) print_help: bool,
+    pub(crate) print_completion: Option<String>,
+    pub(crate) print_profiles: bool,
+    pub(crate) strict: bool,
+    pub(crate) no_boot_classpath: bool,
     pub(crate) nailgun_client: bool,
-    launcher_logfile: Option<PathBuf>,
-    boot_class: Option<OsString>,
-    jdk_home: Option<PathBuf>,
-    classpath_before: Vec<PathBuf>,
-    classpath_after: Vec<PathBuf>,
-    classpath_explicit: Vec<PathBuf>, // What we passed explicitly to the launcher as a classpath.
+    pub(crate) launcher_logfile: Option<PathBuf>,
+    pub(crate) boot_class: Option<OsString>,
+    pub(crate) jdk_home: Option<PathBuf>,
+    pub(crate) java_version_constraint: Option<String>,
+    pub(crate) classpath_before: Vec<PathBuf>,
+    pub(crate) classpath_after: Vec<PathBuf>,
+    pub(crate) classpath_explicit: Vec<PathBuf>, // What we passed explicitly to the launcher as a classpath.
     classpath: Vec<PathBuf>,
-    java_args: Vec<OsString>, // Note: some other fields will also eventually be java args in final command-line.
+    pub(crate) java_args: Vec<OsString>, // Note: some other fields will also eventually be java args in final command-line.
     pub(crate) program_args: Vec<OsString>,
     java_opts: Vec<OsString>,
-    jruby_opts: Vec<OsString>,
     jruby_home: Option<PathBuf>,
+    jruby: JRubyInfo,
+    pub(crate) config: LauncherConfig,
+    unknown_profiles: Vec<String>,
     pub(crate) java_location: Option<PathBuf>,
     pub(crate) java_home: Option<PathBuf>,
+    java: JavaInstallation,
     java_is_modular: bool,
-    java_version: String,
-    java_major_version: u16,
     java_has_appcds: bool,
     use_appcds: bool,
     appcds_autogenerate: bool,
     native_access: bool,
     unsafe_memory: bool,
-    regenerate_jsa_file: bool,
-    xss: Option<OsString>,
+    pub(crate) regenerate_jsa_file: bool,
+    pub(crate) xss: Option<OsString>,
     boot_classpath: Vec<PathBuf>,
     suppress_console: bool,
-    use_jsa_file: bool,
-    remove_jsa_files: bool,
-    log_cds: bool,
+    pub(crate) use_jsa_file: bool,
+    pub(crate) no_cache: bool,
+    pub(crate) remove_jsa_files: bool,
+    pub(crate) log_cds: bool,
     jruby_jsa_file: Option<PathBuf>,
 }
 
-macro_rules! arg_value {
-    ($args:expr) => {{
-        if $args.peek().is_some() {
-            $args.next().to_owned().unwrap()
-        } else {
-            return Err(Box::new(LaunchError {
-                message: "no extra argument",
-            }));
-        }
-    }};
-}
-
-fn grep(file: PathBuf, pattern: &str) -> Option<Vec<String>> {
-    let re = Regex::new(pattern).unwrap();
-    let contents = fs::read_to_string(file);
-
-    if contents.is_ok() {
-        let matches: Vec<String> = contents
-            .unwrap()
-            .lines()
-            .filter(|line| re.is_match(line))
-            .map(|line| line.to_string())
-            .collect();
-        return if matches.is_empty() {
+/// Run `java -XshowSettings:properties -version` and pull out java.version.
+pub(crate) fn probe_java_version(java: &Path) -> Option<String> {
+    let output = Command::new(java)
+        .arg("-XshowSettings:properties")
+        .arg("-version")
+        .output();
+
+    match output {
+        // settings are printed to stderr
+        Ok(output) => parse_java_version_property(&String::from_utf8_lossy(&output.stderr)),
+        Err(e) => {
+            error!("Unable to run {:?}: {}", java, e);
             None
-        } else {
-            Some(matches)
         }
     }
-
-    None
 }
 
-// Note: 1.8 parses as major version 1 but this is ok for the sake of anything we are doing.
-fn major_version(full_version: &str) -> u16 {
-    full_version.split('.').next().unwrap().parse::<u16>().unwrap()
+fn parse_java_version_property(settings: &str) -> Option<String> {
+    settings
+        .lines()
+        .map(|line| line.trim())
+        .find(|line| line.starts_with("java.version ") || line.starts_with("java.version="))
+        .and_then(|line| line.split_once('=').map(|(_, version)| version))
+        .map(|version| version.trim().to_string())
+        .filter(|version| !version.is_empty())
 }
 
 fn is_newer(one: &PathBuf, two: &PathBuf) -> bool {
@@ -175,13 +373,62 @@ fn dir_builder<P: AsRef<Path>>(path: PathBuf, subdirs: Vec<P>) -> PathBuf {
 }
 
 impl LaunchOptions {
-    pub fn parse(&mut self, env: &Environment) -> Result<(), Box<dyn Error>> {
-        if let Some(java_opts) = &env.java_opts {
-            self.java_opts.extend(LaunchOptions::env_as_iter(java_opts))
+    /// Settings from launcher.toml files.  parse() runs after this so anything from the
+    /// environment or argv replaces (or for lists comes after) these.  The Java version and
+    /// JDK home are consulted later in determine_java_location because there environment
+    /// variables have to win over them too.
+    fn apply_config(&mut self) {
+        let config = &self.config;
+
+        self.java_opts.extend(config.java_options.iter().map(OsString::from));
+        self.classpath_before.extend(config.classpath_prepend.iter().cloned());
+        self.classpath_after.extend(config.classpath_append.iter().cloned());
+        self.no_cache = config.cache == Some(false);
+        self.log_cds = config.log_cache.unwrap_or(false);
+        if config.trace.is_some() {
+            self.launcher_logfile = config.trace.clone();
         }
+    }
 
-        if let Some(jruby_opts) = &env.jruby_opts {
-            self.jruby_opts.extend(LaunchOptions::env_as_iter(jruby_opts))
+    /// Apply a profile (and first the profiles it includes) as if its settings were given
+    /// where the profile was.  Includes were checked when config was loaded so only `name`
+    /// itself can be unknown.
+    pub(crate) fn apply_profile(&mut self, name: &str) {
+        let profile = match self.config.profile(name) {
+            Some(profile) => profile,
+            None => {
+                self.unknown_profiles.push(name.to_string());
+                return;
+            }
+        };
+        info!("Applying profile {} ({})", name, profile.origin);
+
+        for include in profile.include.iter() {
+            self.apply_profile(include);
+        }
+
+        self.java_args.extend(profile.java_options.iter().map(OsString::from));
+        self.java_args.extend(profile.properties.iter().map(|(name, value)| OsString::from(format!("-Djruby.{}={}", name, value))));
+        self.classpath_before.extend(profile.classpath_prepend);
+        self.classpath_after.extend(profile.classpath_append);
+        if let Some(cache) = profile.cache {
+            self.no_cache = !cache;
+        }
+        if let Some(log_cache) = profile.log_cache {
+            self.log_cds = log_cache;
+        }
+    }
+
+    /// What -Xprofiles prints.
+    pub fn profiles_report(&self) -> String {
+        self.config.profile_names().iter()
+            .filter_map(|name| self.config.profile(name).map(|profile| profile.describe(name)))
+            .collect()
+    }
+
+    pub fn parse(&mut self, env: &Environment) -> Result<(), Box<dyn Error>> {
+        if let Some(java_opts) = &env.java_opts {
+            self.java_opts.extend(LaunchOptions::env_as_iter("JAVA_OPTS", java_opts)?)
         }
 
         self.parse_os(env);
@@ -194,107 +441,148 @@ impl LaunchOptions {
             self.java_opts.push(java_stack.clone())
         }
 
-        let mut args = env.args.clone().into_iter().peekable();
+        // JRUBY_OPTS behaves as if it came before argv.
+        let mut args = vec![];
+        if let Some(jruby_opts) = &env.jruby_opts {
+            args.extend(LaunchOptions::env_as_iter("JRUBY_OPTS", jruby_opts)?.into_iter().map(|arg| (arg, Origin::JRubyOpts)));
+        }
+        args.extend(env.args.iter().skip(1).map(|arg| (arg.clone(), Origin::CommandLine))); // skip argv0
+        let mistyped = self.parse_arguments(env, args)?;
 
-        args.next().expect("Impossible to not have argv0");
+        if let Some(name) = self.unknown_profiles.first() {
+            return Err(Box::new(LaunchError {
+                message: format!("unknown profile '{}' (known profiles: {})", name, self.config.profile_names().join(", ")),
+            }));
+        }
 
-        while let Some(argument) = args.next() {
-            match argument.to_string_lossy().into_owned().as_str() {
-                "--" => {
-                    self.program_args.push(OsString::from("--"));
-                    self.program_args.extend(args.clone());
-                    break;
-                }
-                // launcher specific -X self...
-                "-Xfork-java" => self.fork_java = true,
-                "-Xcommand" => self.command_only = true,
-                "-Xnobootclasspath" => self.no_boot_classpath = true,
-                "-Xtrace" => self.launcher_logfile = Some(PathBuf::from(arg_value!(args))),
-                "-Xbootclass" => self.boot_class = Some(arg_value!(args)),
-                "-Xjdkhome" => self.jdk_home = Some(PathBuf::from(arg_value!(args))),
-                "-Xcp:p" => self.classpath_before.push(PathBuf::from(arg_value!(args))),
-                "-Xcp:a" => self.classpath_after.push(PathBuf::from(arg_value!(args))),
-                "-Xversion" => {
-                    return Err(Box::new(LaunchError {
-                        message: "need to fix -Xversion",
-                    }))
-                }
-                "-Xhelp" | "-X" => {
-                    // FIXME: WOT
-                    // print_to_console(help)
-                    // if self.append_help.isok puts append_help
-                    self.java_args.push(OsString::from("-Djruby.launcher.nopreamble=true"));
-                    self.program_args.push(OsString::from("-X"));
-                }
-                "-Xproperties" => self.program_args.push(OsString::from("--properties")),
-                // java options we need to pass to java process itself if we see them
-                "-J-cp" | "-J-classpath" => self
-                    .classpath_explicit
-                    .push(PathBuf::from(arg_value!(args))),
-                "--server" => self.java_args.push(OsString::from("-server")),
-                "--client" => self.java_args.push(OsString::from("-client")),
-                "--dev" => {
-                    let dev_args = DEV_MODE_JAVA_OPTIONS.iter().map(|e| OsString::from(e)).into_iter();
-                    self.java_args.extend(dev_args);
-                }
-                "--sample" => self.java_args.push(OsString::from("-Xprof")),
-                "--manage" => {
-                    self.java_args.push(OsString::from("-Dcom.sun.management.jmxremote"));
-                    self.java_args.push(OsString::from("-Djruby.management.enabled=true"))
-                }
-                "--headless" => self.java_args.push(OsString::from("-Djava.awt.headless=true")),
-                "--ng" => self.nailgun_client = true,
-                "--ng-server" => {
-                    self.boot_class = Some(OsString::from("com/martiansoftware/nailgun/NGServer"));
-                    self.java_args.push(OsString::from("-server"));
-                    self.no_boot_classpath = true;
-                }
-                "--no-bootclasspath" => self.no_boot_classpath = true,
-                "-Jea" => {
-                    self.java_args.push(OsString::from("-ea"));
-                    self.no_boot_classpath = true;
-                    println!("Note: -ea option is specified, there will be no bootclasspath in order to enable assertions")
+        for (argument, suggestion) in mistyped {
+            let message = format!("unknown option {} (did you mean {}?)", argument, suggestion);
+
+            if self.strict {
+                return Err(Box::new(LaunchError { message }));
+            }
+            warn!("{}", message);
+            eprintln!("Warning: {}", message);
+        }
+
+        info!("launch options = {:?}", self);
+
+        Ok(())
+    }
+
+    /// Apply launcher options in `args` and pass the rest on to JRuby.  @files on the command
+    /// line and configured aliases are expanded in place.  Returns options which look like
+    /// typos of ours.
+    fn parse_arguments(&mut self, env: &Environment, args: Vec<(OsString, Origin)>) -> Result<Vec<(String, &'static str)>, Box<dyn Error>> {
+        let mut args: VecDeque<(OsString, Origin)> = args.into_iter().collect();
+        let mut mistyped = vec![];
+        // Launcher options and aliases apply anywhere, as they always have.  Past the first
+        // operand (e.g. the script) arguments are not checked for typos or expanded as @files
+        // since they are most likely the script's own.
+        let mut seen_operand = false;
+        let mut value_follows = false; // previous argument was a JRuby option like -I
+
+        while let Some((argument, origin)) = args.pop_front() {
+            let name = argument.to_string_lossy().into_owned();
+            let is_value = std::mem::replace(&mut value_follows, false);
+
+            if name == "--" {
+                self.program_args.push(argument);
+                self.program_args.extend(args.into_iter().map(|(arg, _)| arg));
+                break;
+            }
+
+            if origin == Origin::CommandLine && !seen_operand && !is_value && argfile::is_argfile(&argument) {
+                let expanded = argfile::expand(&argument, env.current_dir.as_deref())
+                    .map_err(|message| LaunchError { message })?;
+
+                for arg in expanded.into_iter().rev() {
+                    args.push_front((arg, Origin::ArgFile));
                 }
-                // FIXME: Implement checkpoint
-                "--cache" => {
-                    if !self.java_has_appcds {
-                        println!("Error: Java {} doesn't support automatic AppCDS", self.java_major_version);
-                        exit(2);
+                continue;
+            }
+
+            // Aliases expand once so one naming another cannot loop.
+            if origin != Origin::Alias && !is_value {
+                let (alias_name, value) = match name.split_once('=') {
+                    Some((alias_name, value)) => (alias_name, Some(value)),
+                    None => (name.as_str(), None),
+                };
+
+                if let Some(alias) = self.config.alias(alias_name) {
+                    let expanded = alias.expand(alias_name, value).map_err(|message| LaunchError { message })?;
+                    info!("Expanding alias {} to {:?}", name, expanded);
+
+                    for arg in expanded.into_iter().rev() {
+                        args.push_front((OsString::from(arg), Origin::Alias));
                     }
-                    self.regenerate_jsa_file = true;
+                    continue;
+                }
+            }
+
+            match option_table::lookup(&name) {
+                Some((option, name_length)) => {
+                    let value = match option.arity {
+                        Arity::Flag => OsString::new(),
+                        Arity::Attached => argument.split_at(name_length).1,
+                        Arity::Separate => match args.pop_front() {
+                            Some((value, _)) => value,
+                            None => return Err(Box::new(LaunchError {
+                                message: format!("{} needs an argument: {}", option.name, option.usage()),
+                            })),
+                        },
+                    };
+
+                    option.validate(&value).map_err(|message| LaunchError { message })?;
+                    (option.apply)(self, value);
                 }
-                "--nocache" => self.use_jsa_file = false,
-                "--rmcache" => self.remove_jsa_files = true,
-                "--logcache" => self.log_cds = true,
-                _ => {
-                    if argument.len() > 2 {
-                        let (two, rest) = argument.split_at(2);
-
-                        let two = two.to_str().unwrap();
-
-                        match two {
-                            "-X" if rest.starts_with(OsString::from("xss")) => self.xss = Some(argument),
-                            "-X" if rest.to_string_lossy().chars().next().unwrap().is_ascii_lowercase() => {
-                                self.java_args.push(OsString::from(format!("-Djruby.{:?}", rest)));
-                            }
-                            "-J" => self.java_args.push(rest),
-                            _ => self.program_args.push(argument),
+                None => {
+                    let suggestion = option_table::suggest(&name).filter(|_| !seen_operand);
+
+                    // Any other -X<lowercase> is a JRuby property.  One which looks like a
+                    // mistyped launcher option is not since its value would become the script.
+                    if name.len() > 2 && name.starts_with("-X") && name[2..].starts_with(|c: char| c.is_ascii_lowercase()) {
+                        if let Some(suggestion) = suggestion {
+                            return Err(Box::new(LaunchError {
+                                message: format!("unknown option {} (did you mean {}?)", name, suggestion),
+                            }));
                         }
+                        self.java_args.push(OsString::from(format!("-Djruby.{}", &name[2..])));
                     } else {
+                        mistyped.extend(suggestion.map(|suggestion| (name.clone(), suggestion)));
+                        seen_operand = seen_operand || (!is_value && !name.starts_with('-'));
+                        value_follows = option_table::JRUBY_OPTIONS_WITH_VALUE.contains(&name.as_str());
                         self.program_args.push(argument);
                     }
                 }
             }
         }
-        info!("launch options = {:?}", self);
 
-        Ok(())
+        Ok(mistyped)
     }
 
+    /// Java comes from (in order): JAVACMD, -Xjdkhome, a runtime bundled with JRuby,
+    /// JAVA_HOME, jdk_home from launcher config, java on PATH (so the one chosen with
+    /// update-alternatives) and only then the best JDK discovery finds.  Discovery also
+    /// replaces a java from PATH which a version constraint or pin rejects.
     fn determine_java_location(&mut self, env: &Environment) -> Result<(), Box<dyn Error>> {
+        let constraint = self.java_version_constraint(env)?;
+        let pinned = if constraint.is_none() { self.pinned_java_version(env) } else { None };
+        // Only a java we went looking for ourselves may be swapped for a better match.
+        let mut explicit = true;
+
         let java = if let Some(cmd) = &env.java_cmd {
             info!("Found JAVACMD");
-            Some(PathBuf::from(cmd))
+            let cmd = PathBuf::from(cmd);
+
+            // JAVACMD=java names a command so look for it on PATH like a shell would.
+            if cmd.components().count() == 1 {
+                Some(find_from_path(&cmd.to_string_lossy(), &env.path, |f| f.exists()).ok_or_else(|| LaunchError {
+                    message: format!("JAVACMD is {} but there is no {} on PATH", cmd.display(), cmd.display()),
+                })?)
+            } else {
+                Some(cmd)
+            }
         } else if self.jdk_home.is_some() {
             info!("-Xjdkhome was specified");
             Some(
@@ -302,51 +590,207 @@ impl LaunchOptions {
                     .join("bin")
                     .join(JAVA_NAME),
             )
+        } else if let Some(runtime) = self.bundled_runtime() {
+            info!("Using runtime bundled with JRuby at {:?}", &runtime);
+            Some(runtime.join("bin").join(JAVA_NAME))
         } else if let Some(home) = &env.java_home {
             info!("Deriving from JAVA_HOME");
             Some(PathBuf::from(home).join("bin").join(JAVA_NAME))
+        } else if let Some(jdk_home) = &self.config.jdk_home {
+            info!("Using jdk_home from launcher config");
+            Some(jdk_home.join("bin").join(JAVA_NAME))
+        } else if let Some(java) = find_from_path(JAVA_NAME, &env.path, |f| f.exists()) {
+            info!("Found java command on Path");
+            explicit = false;
+            Some(java)
         } else {
-            info!("Trying to find java command on Path");
-            find_from_path(JAVA_NAME, &env.path, |f| f.exists())
+            explicit = false;
+            self.discover_jdk(env, constraint.as_ref().or(pinned.as_ref())).map(|jdk| {
+                info!("Using discovered JDK {:?} ({})", &jdk.home, &jdk.version);
+                jdk.home.join("bin").join(JAVA_NAME)
+            })
+        };
+
+        let mut java = match java {
+            Some(java) => java,
+            None => {
+                return Err(Box::new(LaunchError {
+                    message: "unable to find java (set JAVA_HOME or JAVACMD, or use -Xjdkhome)".to_string(),
+                }))
+            }
         };
 
-        // Panic on pathological env setting is ok here as the error should be explanatory.
-        if let Some(loc) = java.clone() {
-            let parent = loc.parent().unwrap().parent().unwrap();
-            info!("JAVA_HOME = {}", &parent.display());
-            self.java_home = Some(parent.to_owned());
+        self.inspect_java(env, &java)?;
 
+        if let Some(constraint) = &constraint {
+            if !constraint.matches(self.java_version()) {
+                if explicit {
+                    return Err(Box::new(LaunchError {
+                        message: format!("Java {} at {} does not satisfy the required Java version '{}'",
+                                         self.java_version(), java.display(), constraint),
+                    }));
+                }
+
+                info!("Java {} does not satisfy '{}'.  Looking for another JDK", self.java_version(), constraint);
+                match self.discover_jdk(env, Some(constraint)) {
+                    Some(jdk) => {
+                        info!("Falling back to discovered JDK {:?} ({})", &jdk.home, &jdk.version);
+                        java = jdk.home.join("bin").join(JAVA_NAME);
+                        self.inspect_java(env, &java)?;
+                    }
+                    None => {
+                        return Err(Box::new(LaunchError {
+                            message: format!("Java {} at {} does not satisfy the required Java version '{}' and no installed JDK does",
+                                             self.java_version(), java.display(), constraint),
+                        }));
+                    }
+                }
+            }
         }
 
-        self.java_is_modular = self.java_is_modular();
+        // A project's pin only says which Java it would like.  It never overrides a Java the
+        // user chose and never stops a launch.
+        if let Some(pinned) = pinned.filter(|pinned| !explicit && !pinned.matches(self.java_version())) {
+            match self.discover_jdk(env, Some(&pinned)) {
+                Some(jdk) => {
+                    info!("Using discovered JDK {:?} ({}) for the pinned Java version {}", &jdk.home, &jdk.version, pinned);
+                    java = jdk.home.join("bin").join(JAVA_NAME);
+                    self.inspect_java(env, &java)?;
+                }
+                None => {
+                    warn!("No installed JDK matches the pinned Java version {}", pinned);
+                    eprintln!("Warning: this project pins Java {} but no installed JDK matches.  Using Java {} at {}",
+                              pinned, self.java_version(), java.display());
+                }
+            }
+        }
 
-        if let Some(version) = self.find_java_version() {
-            self.java_version = version
-        } else {
-            panic!("Cannot determine a Java home directory");
+        if let Some(reason) = &self.java.incompatibility {
+            warn!("{:?} is probably not runnable: {}", &java, reason);
+            eprintln!("Warning: Java at {} may not run on this machine: {}", java.display(), reason);
         }
 
-        self.java_major_version = major_version(self.java_version.as_str());
         self.make_version_decisions();
-        self.java_has_appcds = self.java_has_appcds();
+        self.java_has_appcds = self.java.has_appcds();
         self.use_appcds = self.java_has_appcds;
-        self.use_jsa_file = self.use_appcds;
+        self.use_jsa_file = self.use_appcds && !self.no_cache;
+        self.java.log_diagnostics();
         info!("MODULAR: {}", self.java_is_modular);
-        info!("VERSION: {}", self.java_version);
-        info!("MAJOR_VERSION: {}", self.java_major_version);
+        info!("VERSION: {}", self.java_version());
+        info!("MAJOR_VERSION: {}", self.java_version().major());
         info!("Java has CDS: {}", self.java_has_appcds);
 
-        // FIXME: Seemingly if not found on path we should probably just exit with an error here.
-        self.java_location = java;
+        // --cache is parsed before we know which Java we have.
+        if self.regenerate_jsa_file && !self.java_has_appcds {
+            let message = format!("--cache needs AppCDS but Java {} at {} does not support it", self.java_version(), java.display());
+            self.java_location = Some(java);
+            return Err(Box::new(LaunchError { message }));
+        }
+
+        self.java_location = Some(java);
+
+        Ok(())
+    }
+
+    /// Self-contained distributions ship a (usually jlink'd) runtime within JRuby home.
+    fn bundled_runtime(&self) -> Option<PathBuf> {
+        self.jruby_home.as_ref()?;
+
+        BUNDLED_RUNTIME_DIRS
+            .iter()
+            .map(|dir| self.jruby_home(vec![dir]))
+            .find(|runtime| runtime.join("bin").join(JAVA_NAME).exists())
+    }
+
+    /// -Xjava-version wins over JRUBY_JAVA_VERSION which wins over launcher config.  A Java
+    /// which does not satisfy these is an error.
+    fn java_version_constraint(&self, env: &Environment) -> Result<Option<VersionConstraint>, Box<dyn Error>> {
+        let (constraint, source) = if let Some(constraint) = &self.java_version_constraint {
+            (constraint.clone(), "-Xjava-version".to_string())
+        } else if let Some(constraint) = &env.jruby_java_version {
+            (constraint.to_string_lossy().into_owned(), "JRUBY_JAVA_VERSION".to_string())
+        } else if let Some((constraint, file)) = &self.config.java_version {
+            (constraint.clone(), file.display().to_string())
+        } else {
+            return Ok(None);
+        };
+
+        info!("Java version constraint from {}: {}", source, constraint);
+        match VersionConstraint::parse(&constraint) {
+            Some(constraint) => Ok(Some(constraint)),
+            None => Err(Box::new(LaunchError {
+                message: format!("invalid Java version constraint '{}' from {} (expected something like '>=17,<22' or '21')",
+                                 constraint, source),
+            })),
+        }
+    }
+
+    /// A project's .java-version or .tool-versions.  Unlike java_version_constraint it is only
+    /// a preference so one we cannot read is ignored.
+    fn pinned_java_version(&self, env: &Environment) -> Option<VersionConstraint> {
+        let pinned = env.current_dir.as_ref().and_then(|dir| find_pinned_version(dir))?;
+        info!("Pinned Java version from {}: {}", pinned.file.display(), pinned.version);
+
+        let constraint = VersionConstraint::parse(&pinned.version);
+        if constraint.is_none() {
+            warn!("Ignoring unusable Java version '{}' pinned in {}", pinned.version, pinned.file.display());
+        }
+        constraint
+    }
+
+    fn discover_jdk(&self, env: &Environment, constraint: Option<&VersionConstraint>) -> Option<DiscoveredJdk> {
+        jdk_discovery::discover(env)
+            .into_iter()
+            .find(|jdk| match constraint {
+                Some(constraint) => constraint.matches(&jdk.version),
+                None => true,
+            })
+    }
+
+    /// Figure out home, modularity and version for a java executable.
+    fn inspect_java(&mut self, env: &Environment, java: &Path) -> Result<(), Box<dyn Error>> {
+        // /usr/bin/java is usually a chain of symlinks into the real JDK.
+        let loc = resolve_symlinks(java);
+        let parent = loc.parent().and_then(Path::parent).ok_or_else(|| LaunchError {
+            message: format!("unable to find the Java home for {} (expected java in <java home>/bin)", java.display()),
+        })?;
+        info!("JAVA_HOME = {}", &parent.display());
+        self.java_home = Some(parent.to_owned());
+
+        let cache = ProbeCache::new(env);
+        if let Some(java) = cache.as_ref().and_then(|cache| cache.load(parent)) {
+            self.java = java;
+        } else {
+            self.java = JavaInstallation::from_home(parent);
+
+            if let Some(version) = self.find_java_version(java) {
+                self.java.java_version = Some(version);
+            } else {
+                error!("No release file, no known layout and no usable version from {:?}", java);
+                return Err(Box::new(LaunchError {
+                    message: format!("unable to determine the version of the Java at {}", java.display()),
+                }));
+            }
+
+            // Reading bin/java for this is why the verdict is cached with everything else.
+            self.java.incompatibility = incompatibility(&self.java, java);
 
+            if let Some(cache) = &cache {
+                cache.store(parent, &self.java);
+            }
+        }
+
+        self.java_is_modular = self.java.is_modular();
 
         Ok(())
     }
 
     fn make_version_decisions(&mut self) {
-        self.appcds_autogenerate = self.java_major_version >= 19;
-        self.native_access = self.java_major_version >= 22;
-        self.unsafe_memory = self.java_major_version >= 23;
+        let major = self.java_version().major();
+
+        self.appcds_autogenerate = major >= 19;
+        self.native_access = major >= 22;
+        self.unsafe_memory = major >= 23;
 
 
         info!("APPCDS auto generate: {}", self.appcds_autogenerate);
@@ -354,55 +798,113 @@ impl LaunchOptions {
         info!("Unsafe memory: {}", self.unsafe_memory);
     }
 
-    fn java_is_modular(&self) -> bool {
-        self.java_home(vec!["lib", "modules"]).exists()
-            || self.java_home(vec!["release"]).exists()
+    pub(crate) fn java_version(&self) -> &JavaVersion {
+        self.java.java_version.as_ref().unwrap_or(&UNKNOWN_JAVA_VERSION)
     }
 
-    fn java_home<P: AsRef<Path>>(&self, subdirs: Vec<P>) -> PathBuf {
-        dir_builder(self.java_home.to_owned().unwrap(), subdirs)
+    /// Java options from the launcher.d fragments which apply to the Java we found.
+    fn fragment_options(&self, env: &Environment) -> Result<Vec<OsString>, Box<dyn Error>> {
+        let dirs = option_fragments::fragment_dirs(env, self.jruby_home.as_ref().unwrap());
+        let fragments = option_fragments::load(&dirs).map_err(|message| LaunchError { message })?;
+        let mut options = vec![];
+
+        for fragment in fragments {
+            if fragment.applies_to(self.java_version()) {
+                info!("Option fragment {:?} adds {:?}", fragment.file, fragment.options);
+                options.extend(fragment.options);
+            } else {
+                info!("Option fragment {:?} skipped: Java {} is not {}", fragment.file, self.java_version(),
+                      fragment.java_version.unwrap());
+            }
+        }
+
+        Ok(options)
     }
 
     fn jruby_home<P: AsRef<Path>>(&self, subdirs: Vec<P>) -> PathBuf {
         dir_builder(self.jruby_home.to_owned().unwrap(), subdirs)
     }
 
-    fn find_java_version(&mut self) -> Option<String> {
-        let release_file = self.java_home(vec!["release"]);
+    /// Version comes from (in order): the release file, well-known layout markers and
+    /// finally asking java itself.  The last one is slow so it is only a last resort.
+    fn find_java_version(&self, java: &Path) -> Option<JavaVersion> {
+        if let Some(version) = &self.java.java_version {
+            info!("Java version from release file: {}", version);
+            return Some(version.clone());
+        }
 
-        if release_file.exists() {
-            if let Some(lines) = grep(release_file, "^JAVA_VERSION=") {
-                if !lines.is_empty() {
-                    let re = Regex::new(r"JAVA_VERSION=\s*\D*([\d.]+)").unwrap();
-                    let line = lines.first().unwrap();
-                    if let Some(capture) = re.captures(line).map(|c| c.get(1)) {
-                        return capture.map(|m| m.as_str().to_string());
-                    }
-                }
-            }
+        // Anything with the pre-module layout we treat as 8.
+        if self.java.has_legacy_layout() {
+            info!("No release file but found lib/rt.jar.  Assuming Java 8");
+            return JavaVersion::parse("1.8");
         }
 
-        None
+        info!("No release file or known layout.  Asking {:?}", java);
+        probe_java_version(java).and_then(|v| JavaVersion::parse(&v))
     }
 
-    fn java_has_appcds(&mut self) -> bool {
-        let server_dir = self.java_home(vec![JSA_DIR, "server"]);
+    fn validate_installation(&self) -> Result<(), Box<dyn Error>> {
+        let jruby_home = self.jruby_home.as_ref().unwrap();
+        let problems = jruby_installation::validate(jruby_home, self.java_is_modular);
 
-        if server_dir.exists() && fs::metadata(&server_dir).unwrap().is_dir() {
-            let entries = fs::read_dir(server_dir)
-                .unwrap()
-                .map(|res| res.map(|e| e.path()));
+        if problems.is_empty() {
+            return Ok(());
+        }
 
-            return entries
-                .filter(|e| e.is_ok())
-                .any(|f| f.unwrap().to_string_lossy().ends_with(".jsa"));
+        for problem in &problems {
+            error!("Invalid JRuby installation: {}", problem);
         }
 
-        false
+        Err(Box::new(LaunchError {
+            message: format!("JRuby installation at {} is not usable:\n  {}", jruby_home.display(), problems.join("\n  ")),
+        }))
+    }
+
+    fn read_jruby_info(&mut self, env: &Environment) {
+        let jar = JRUBY_JARS.iter()
+            .map(|jar| self.jruby_home(vec!["lib", jar]))
+            .find(|jar| jar.exists());
+
+        if let Some(jar) = jar {
+            let cache = ProbeCache::new(env);
+
+            self.jruby = match cache.as_ref().and_then(|cache| cache.load_jruby(&jar)) {
+                Some(jruby) => jruby,
+                None => {
+                    let jruby = JRubyInfo::from_jar(&jar);
+                    if let Some(cache) = &cache {
+                        cache.store_jruby(&jar, &jruby);
+                    }
+                    jruby
+                }
+            };
+        }
+        info!("JRuby version: {:?}", self.jruby.version);
+        info!("JRuby minimum Java: {:?}", self.jruby.minimum_java);
+    }
+
+    /// Refuse a Java older than what the JRuby we are launching was built for.
+    fn check_jruby_requirements(&mut self, env: &Environment) -> Result<(), Box<dyn Error>> {
+        self.read_jruby_info(env);
+
+        if let Some(minimum) = self.jruby.minimum_java {
+            if self.java_version().major() < minimum {
+                return Err(Box::new(LaunchError {
+                    message: format!("JRuby {} needs Java {} or newer but the selected Java {} at {} is too old",
+                                     self.jruby.version.as_deref().unwrap_or("(unknown version)"), minimum, self.java_version(),
+                                     self.java_home.as_ref().unwrap().display()),
+                }));
+            }
+        }
+
+        Ok(())
     }
 
     fn prepare_options(&mut self, env: &Environment) -> Result<(), Box<dyn Error>> {
-        let mut java_options: Vec<OsString> = self.java_opts.clone();
+        // Fragments go first so anything the user passes can still override them.
+        let mut java_options: Vec<OsString> = self.fragment_options(env)?;
+        java_options.extend(self.java_opts.iter().cloned());
+        java_options.extend(self.java_args.iter().cloned());
 
         if let Some(jdk_home) = &self.jdk_home {
             java_options.push(OsString::from(format!("-Djdk.home={}", jdk_home.display())));
@@ -418,9 +920,12 @@ impl LaunchOptions {
         let jni_dir = self.jruby_home(vec!["lib", "jni"]);
         java_options.push(OsString::from(format!("-Djffi.boot.library.path={}", jni_dir.display())));
 
-        if self.xss.is_none() {
-            info!("No explicit xss. Defaulting to: {}", XSS_DEFAULT);
-            java_options.push(OsString::from(XSS_DEFAULT_OPT));
+        match &self.xss {
+            Some(xss) => java_options.push(xss.clone()),
+            None => {
+                info!("No explicit xss. Defaulting to: {}", XSS_DEFAULT);
+                java_options.push(OsString::from(XSS_DEFAULT_OPT));
+            }
         }
 
         // construct_boot_classpath
@@ -435,11 +940,12 @@ impl LaunchOptions {
         } else if jruby_complete_jar.exists() {
             self.add_to_boot_class_path(jruby_complete_jar, false);
         } else {
+            // validate_installation would have stopped us before now.
             warn!("No jruby.jar or jruby-complete.jar found.")
-            // not in original launcher (maybe verify via CLASSPATH and other potential places they could be set?
         }
 
         // construct_classpath
+        self.classpath.extend(self.classpath_before.to_owned());
         self.add_jars_to_classpath();
 
         if self.classpath_explicit.is_empty() {
@@ -490,26 +996,26 @@ impl LaunchOptions {
         }
 
         if self.java_is_modular {
-            let module_opts = self.jruby_home(vec!["bin", ".jruby.module_opts"]);
-            info!("MOF: {:?}", module_opts);
+            let module_opts = self.jruby_home(vec!["bin", MODULE_OPTS_FILE]);
 
-            if module_opts.exists() {
+            let module_options = if module_opts.exists() {
                 info!("Found module options file {:?}.  Using that.", module_opts);
-                java_options.push(OsString::from(format!("@{}", module_opts.display())));
+                module_opts::read(&module_opts).map_err(|message| LaunchError { message })?
             } else {
                 info!("Found no module options file.  Use hard-coded values.");
-                java_options.push(OsString::from("--add-opens"));
-                java_options.push(OsString::from("java.base/java.io=org.jruby.dist"));
-                java_options.push(OsString::from("--add-opens"));
-                java_options.push(OsString::from("java.base/java.nio.channels=org.jruby.dist"));
-                java_options.push(OsString::from("--add-opens"));
-                java_options.push(OsString::from("java.base/sun.nio.ch=org.jruby.dist"));
-                java_options.push(OsString::from("--add-opens"));
-                java_options.push(OsString::from("java.management/sun.management=org.jruby.dist"));
-            }
+                let defaults: Vec<OsString> = DEFAULT_MODULE_OPTIONS.iter().map(OsString::from).collect();
+                module_opts::parse(&defaults).map_err(|message| LaunchError { message })?
+            };
+
+            let module_options = module_opts::merge(module_options, &java_options, &self.java);
+            info!("Module options: {:?}", module_options);
+            java_options.extend(module_options);
         }
 
-        let jsa_file_name = format!("jruby-java{}.jsa", &self.java_version);
+        let jsa_file_name = match &self.jruby.version {
+            Some(jruby_version) => format!("jruby-{}-java{}.jsa", jruby_version, self.java_version()),
+            None => format!("jruby-java{}.jsa", self.java_version()),
+        };
         let jsa_file_name = jsa_file_name.as_str();
         let jsa_file = self.jruby_home(vec!["lib", jsa_file_name]);
         self.jruby_jsa_file = if let Some(jsa_env_file) = &env.jruby_jsa_file {
@@ -609,7 +1115,7 @@ impl LaunchOptions {
         let lib_dir = self.jruby_home.clone().unwrap().join("lib");
 
         if !lib_dir.is_dir() {
-            // FIXME: This should full on abort
+            // validate_installation would have stopped us before now.
             error!("{:?} is not a directory...skipping!", lib_dir);
             return;
         }
@@ -680,6 +1186,20 @@ impl LaunchOptions {
         result.ok();
     }
 
+    /// What -Xversion prints.  Everything support needs to know about this launch.
+    pub fn version_report(&self) -> String {
+        let unknown = "unknown".to_string();
+
+        format!("jruby launcher {} ({}, rev {})\nJRuby home: {}\nJRuby version: {}\nJava version: {}\nJava location: {}\n",
+                env!("CARGO_PKG_VERSION"),
+                env!("JRUBY_LAUNCHER_TARGET"),
+                env!("JRUBY_LAUNCHER_GIT_REVISION"),
+                self.jruby_home.as_ref().map_or(unknown.clone(), |home| home.display().to_string()),
+                self.jruby.version.as_ref().unwrap_or(&unknown),
+                self.java.java_version.as_ref().map_or(unknown.clone(), |version| version.to_string()),
+                self.java_location.as_ref().map_or(unknown.clone(), |java| java.display().to_string()))
+    }
+
     pub fn command_line(&self) -> Vec<OsString> {
         let mut command_line = self.java_opts.clone();
 
@@ -688,9 +1208,16 @@ impl LaunchOptions {
         command_line
     }
 
-    fn env_as_iter(value: &OsString) -> Vec<OsString> {
-        // FIXME: Some off quote removal but only for first/last char of string
-        value.split_ascii_whitespace().collect()
+    /// What -Xcommand prints: the java command quoted so a shell could run it.
+    pub fn command_report(&self) -> OsString {
+        let mut command = vec![self.java_location.clone().unwrap_or_default().into_os_string()];
+
+        command.extend(self.command_line());
+        join_quoted(&command)
+    }
+
+    fn env_as_iter(name: &str, value: &OsString) -> Result<Vec<OsString>, Box<dyn Error>> {
+        value.shell_split().map_err(|e| LaunchError { message: format!("{}: {}", name, e) }.into())
     }
 
     #[cfg(unix)]
@@ -732,3 +1259,394 @@ impl LaunchOptions {
         }
     }
 }
+
+fn join_quoted(words: &[OsString]) -> OsString {
+    let mut joined = OsString::new();
+
+    for (i, word) in words.iter().enumerate() {
+        if i > 0 {
+            joined.push(" ");
+        }
+        joined.push(word.shell_quote());
+    }
+
+    joined
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::ffi::OsString;
+    use std::path::PathBuf;
+    use crate::file_helper::tests::temp_dir;
+    use crate::environment::tests::empty_env;
+    use crate::launch_options::{parse_java_version_property, JavaVersion, LaunchOptions, Origin, DEV_MODE_JAVA_OPTIONS, JAVA_NAME};
+
+    fn version(version: &str) -> JavaVersion {
+        JavaVersion::parse(version).unwrap()
+    }
+
+    #[test]
+    fn test_java_version_legacy() {
+        let v = version("1.8.0_292");
+        assert_eq!(v.major(), 8);
+        assert_eq!(v.components(), &[8, 0, 292]);
+        assert!(!v.is_pre_release());
+
+        let v = version("1.8.0_292-b10");
+        assert_eq!(v.components(), &[8, 0, 292]);
+        assert!(!v.is_pre_release());
+
+        assert_eq!(version("1.8").major(), 8);
+        assert_eq!(version("1.8.0-ea").major(), 8);
+        assert!(version("1.8.0-ea").is_pre_release());
+    }
+
+    #[test]
+    fn test_java_version_jep223() {
+        assert_eq!(version("17").major(), 17);
+        assert_eq!(version("17.0.2").components(), &[17, 0, 2]);
+        assert_eq!(version("21+35").major(), 21);
+        assert_eq!(version("21.0.1+12-LTS").components(), &[21, 0, 1]);
+        assert!(version("23-ea").is_pre_release());
+        assert_eq!(version("23-ea").major(), 23);
+        assert_eq!(version("24-ea+5-123").major(), 24);
+        assert_eq!(version("23-ea").to_string(), "23-ea");
+    }
+
+    #[test]
+    fn test_java_version_invalid() {
+        assert!(JavaVersion::parse("").is_none());
+        assert!(JavaVersion::parse("potato").is_none());
+        assert!(JavaVersion::parse("1.8.0_x").is_none());
+    }
+
+    #[test]
+    fn test_java_version_ordering() {
+        assert!(version("1.8.0_292") < version("11"));
+        assert!(version("1.8.0_292") > version("1.8.0_72"));
+        assert!(version("17.0.2") > version("17.0.1"));
+        assert!(version("23-ea") < version("23"));
+        assert!(version("23-ea") > version("22.0.2"));
+        assert!(version("21+35") < version("21.0.1+12"));
+        assert!(version("21.0.1+12") < version("21.0.1+13"));
+        assert_eq!(version("21"), version("21.0.0"));
+    }
+
+    #[test]
+    fn test_parse_java_version_property() {
+        let settings = "Property settings:\n    file.encoding = UTF-8\n    java.vendor = Eclipse Adoptium\n    \
+            java.version = 17.0.2\n    java.version.date = 2022-01-18\n\nopenjdk version \"17.0.2\"";
+
+        assert_eq!(parse_java_version_property(settings), Some("17.0.2".to_string()));
+        assert_eq!(parse_java_version_property("java.vendor = Oracle"), None);
+        assert_eq!(parse_java_version_property("    java.version = "), None);
+    }
+
+    #[test]
+    fn test_bundled_runtime() {
+        let home = temp_dir("bundled_runtime");
+        fs::create_dir_all(home.join("runtime").join("bin")).unwrap();
+        fs::create_dir_all(home.join("jre")).unwrap();
+
+        let options = LaunchOptions { jruby_home: Some(home.to_path_buf()), ..Default::default() };
+        let missing = options.bundled_runtime();
+
+        fs::write(home.join("runtime").join("bin").join(JAVA_NAME), "").unwrap();
+        let found = options.bundled_runtime();
+
+        assert_eq!(missing, None);
+        assert_eq!(found, Some(home.join("runtime")));
+    }
+
+    #[test]
+    fn test_parse_jruby_opts() {
+        let mut env = empty_env();
+        env.args = vec!["jruby".into(), "-e".into(), "1".into()];
+        env.jruby_opts = Some("--dev -J-Xmx2g -Xcp:a extra.jar --disable-gems -Xcompile.mode=OFF".into());
+
+        let mut options = LaunchOptions::default();
+        options.parse(&env).unwrap();
+
+        assert!(options.java_args.contains(&OsString::from("-Xmx2g")));
+        assert!(options.java_args.contains(&OsString::from(DEV_MODE_JAVA_OPTIONS[0])));
+        assert_eq!(options.classpath_after, vec![PathBuf::from("extra.jar")]);
+        assert!(options.java_args.contains(&OsString::from("-Djruby.compile.mode=OFF")));
+        assert_eq!(options.program_args, vec![OsString::from("--disable-gems"), OsString::from("-e"), OsString::from("1")]);
+    }
+
+    #[test]
+    fn test_parse_argfile() {
+        let dir = temp_dir("parse_argfile");
+        fs::write(dir.join("opts.txt"), "-J-Xmx1g -Xcp:a a.jar\n").unwrap();
+
+        let mut env = empty_env();
+        env.current_dir = Some(dir.to_path_buf());
+        env.args = ["jruby", "-J-Xmx2g", "@opts.txt", "-I", "@lib", "script.rb", "@opts.txt"].iter().map(OsString::from).collect();
+
+        let mut options = LaunchOptions::default();
+        let result = options.parse(&env);
+        result.unwrap();
+
+        // as if inline so the file's -Xmx1g comes last and wins
+        assert_eq!(options.java_args, vec![OsString::from("-Xmx2g"), OsString::from("-Xmx1g")]);
+        assert_eq!(options.classpath_after, vec![PathBuf::from("a.jar")]);
+        assert_eq!(options.program_args, vec![OsString::from("-I"), OsString::from("@lib"),
+                                              OsString::from("script.rb"), OsString::from("@opts.txt")]);
+    }
+
+    #[test]
+    fn test_parse_quoted_env() {
+        let mut env = empty_env();
+        env.args = vec!["jruby".into()];
+        env.java_opts = Some("-Dapp.name=\"My App\" -Xmx1g".into());
+        env.jruby_opts = Some("-J-Dtitle='a b' -e 'puts 1'".into());
+
+        let mut options = LaunchOptions::default();
+        options.parse(&env).unwrap();
+
+        assert!(options.java_opts.starts_with(&[OsString::from("-Dapp.name=My App"), OsString::from("-Xmx1g")]));
+        assert!(options.java_args.contains(&OsString::from("-Dtitle=a b")));
+        assert_eq!(options.program_args, vec![OsString::from("-e"), OsString::from("puts 1")]);
+
+        env.java_opts = Some("-Dapp.name=\"My App".into());
+        let error = LaunchOptions::default().parse(&env).unwrap_err();
+        assert!(error.to_string().contains("JAVA_OPTS: unbalanced"), "{}", error);
+    }
+
+    #[test]
+    fn test_config_precedence() {
+        let mut env = empty_env();
+        env.args = vec!["jruby".into(), "-J-Xmx3g".into()];
+        env.java_opts = Some("-Xmx2g".into());
+        env.jruby_java_version = Some("17".into());
+
+        let mut options = LaunchOptions::default();
+        options.config.java_options = vec!["-Xmx1g".to_string()];
+        options.config.java_version = Some(("21".to_string(), PathBuf::from("launcher.toml")));
+        options.config.cache = Some(false);
+        options.apply_config();
+        options.parse(&env).unwrap();
+
+        // later wins for java so config, then JAVA_OPTS, then argv
+        let heap: Vec<&OsString> = options.java_opts.iter().chain(options.java_args.iter())
+            .filter(|opt| opt.to_string_lossy().starts_with("-Xmx"))
+            .collect();
+        assert_eq!(heap, vec!["-Xmx1g", "-Xmx2g", "-Xmx3g"]);
+        assert!(options.no_cache);
+        assert_eq!(options.java_version_constraint(&env).unwrap().unwrap().to_string(), "17");
+
+        env.jruby_java_version = None;
+        assert_eq!(options.java_version_constraint(&env).unwrap().unwrap().to_string(), "21");
+    }
+
+    #[test]
+    fn test_cache_needs_appcds() {
+        let dir = temp_dir("cache_needs_appcds");
+        let jdk = dir.join("jdk8");
+        fs::create_dir_all(jdk.join("bin")).unwrap();
+        fs::write(jdk.join("bin").join(JAVA_NAME), "").unwrap();
+        fs::write(jdk.join("release"), "JAVA_VERSION=\"1.8.0_402\"\n").unwrap();
+
+        let mut env = empty_env();
+        env.args = vec!["jruby".into(), "--cache".into()];
+        let mut options = LaunchOptions { jdk_home: Some(jdk.clone()), ..Default::default() };
+        options.parse(&env).unwrap();
+        let located = options.determine_java_location(&env);
+
+        assert!(options.regenerate_jsa_file);
+        assert!(located.unwrap_err().to_string().contains("--cache needs AppCDS but Java 1.8.0_402"));
+    }
+
+    #[test]
+    fn test_java_cmd_errors() {
+        let dir = temp_dir("java_cmd");
+        let jdk = dir.join("jdk17");
+        fs::create_dir_all(jdk.join("bin")).unwrap();
+        fs::write(jdk.join("bin").join(JAVA_NAME), "").unwrap();
+        fs::write(jdk.join("release"), "JAVA_VERSION=\"17.0.2\"\n").unwrap();
+
+        let mut env = empty_env();
+        env.java_cmd = Some(JAVA_NAME.into());
+        env.path = Some(jdk.join("bin").into_os_string());
+        let mut options = LaunchOptions::default();
+        let on_path = options.determine_java_location(&env);
+        let found = options.java_location.clone();
+
+        env.path = None;
+        let not_on_path = LaunchOptions::default().determine_java_location(&env);
+        let no_home = LaunchOptions::default().inspect_java(&env, &PathBuf::from(JAVA_NAME));
+
+        assert!(on_path.is_ok(), "{:?}", on_path);
+        assert_eq!(found, Some(jdk.join("bin").join(JAVA_NAME)));
+        assert!(not_on_path.unwrap_err().to_string().contains("no java"));
+        assert!(no_home.unwrap_err().to_string().contains("unable to find the Java home"));
+    }
+
+    #[test]
+    fn test_pinned_version_is_a_preference() {
+        let dir = temp_dir("pinned_version");
+        let jdk = dir.join("jdk21");
+        fs::create_dir_all(jdk.join("bin")).unwrap();
+        fs::write(jdk.join("bin").join(JAVA_NAME), "").unwrap();
+        fs::write(jdk.join("release"), "JAVA_VERSION=\"21.0.2\"\n").unwrap();
+        fs::write(dir.join(".java-version"), "17\n").unwrap();
+
+        let mut env = empty_env();
+        env.current_dir = Some(dir.to_path_buf());
+        let mut options = LaunchOptions { jdk_home: Some(jdk.clone()), ..Default::default() };
+        let constraint = options.java_version_constraint(&env).unwrap();
+        let pinned = options.pinned_java_version(&env);
+        let located = options.determine_java_location(&env);
+
+        assert!(constraint.is_none());
+        assert_eq!(pinned.unwrap().to_string(), "17");
+        assert!(located.is_ok(), "{:?}", located);
+        assert_eq!(options.java_location, Some(jdk.join("bin").join(JAVA_NAME)));
+        assert_eq!(options.java_version().major(), 21);
+    }
+
+    #[test]
+    fn test_pin_does_not_override_java_home() {
+        let dir = temp_dir("pin_java_home");
+        let java_home = dir.join("jdk21");
+        let pinned = dir.join(".jdks").join("jdk17");
+        for (jdk, version) in [(&java_home, "21.0.2"), (&pinned, "17.0.10")] {
+            fs::create_dir_all(jdk.join("bin")).unwrap();
+            fs::write(jdk.join("bin").join(JAVA_NAME), "").unwrap();
+            fs::write(jdk.join("release"), format!("JAVA_VERSION=\"{}\"\n", version)).unwrap();
+        }
+        fs::write(dir.join(".java-version"), "17\n").unwrap();
+
+        let mut env = empty_env();
+        env.current_dir = Some(dir.to_path_buf());
+        env.home = Some(dir.as_os_str().to_os_string());
+        env.java_home = Some(java_home.as_os_str().to_os_string());
+        let mut options = LaunchOptions::default();
+        let located = options.determine_java_location(&env);
+
+        assert!(located.is_ok(), "{:?}", located);
+        assert_eq!(options.java_location, Some(java_home.join("bin").join(JAVA_NAME)));
+        assert_eq!(options.java_version().major(), 21);
+    }
+
+    #[test]
+    fn test_path_before_discovery() {
+        let dir = temp_dir("path_before_discovery");
+        let on_path = dir.join("usr");
+        let discovered = dir.join(".jdks").join("jdk21");
+        for (jdk, version) in [(&on_path, "17.0.10"), (&discovered, "21.0.2")] {
+            fs::create_dir_all(jdk.join("bin")).unwrap();
+            fs::write(jdk.join("bin").join(JAVA_NAME), "").unwrap();
+            fs::write(jdk.join("release"), format!("JAVA_VERSION=\"{}\"\n", version)).unwrap();
+        }
+
+        let mut env = empty_env();
+        env.home = Some(dir.as_os_str().to_os_string());
+        env.path = Some(on_path.join("bin").into_os_string());
+        let mut options = LaunchOptions::default();
+        options.determine_java_location(&env).unwrap();
+        let chosen = options.java_location.clone();
+
+        // PATH's java only loses when it does not satisfy the required version.
+        env.jruby_java_version = Some("21".into());
+        let mut options = LaunchOptions::default();
+        options.determine_java_location(&env).unwrap();
+
+        assert_eq!(chosen, Some(on_path.join("bin").join(JAVA_NAME)));
+        assert_eq!(options.java_location, Some(discovered.join("bin").join(JAVA_NAME)));
+    }
+
+    #[test]
+    fn test_profiles() {
+        let mut env = empty_env();
+        env.args = ["jruby", "--manage", "-Xprofile=ci", "-e", "1"].iter().map(OsString::from).collect();
+
+        let mut options = LaunchOptions::default();
+        options.config.merge(&PathBuf::from("/etc/jruby/launcher.toml"), r#"
+            [profiles.ci]
+            include = ["headless"]
+            java_options = ["-Xmx1g"]
+            properties = { "compile.mode" = "OFF" }
+            classpath_append = ["ci.jar"]
+            cache = false
+        "#).unwrap();
+        options.parse(&env).unwrap();
+
+        assert_eq!(options.java_args, ["-Dcom.sun.management.jmxremote", "-Djruby.management.enabled=true",
+                                       "-Djava.awt.headless=true", "-Xmx1g", "-Djruby.compile.mode=OFF"]);
+        assert_eq!(options.classpath_after, vec![PathBuf::from("/etc/jruby/ci.jar")]);
+        assert!(options.no_cache);
+
+        env.args = ["jruby", "-Xprofile", "server", "-e", "1"].iter().map(OsString::from).collect();
+        let mut options = LaunchOptions::default();
+        options.parse(&env).unwrap();
+        assert_eq!(options.java_args, ["-server"]);
+        assert_eq!(options.program_args, ["-e", "1"]);
+
+        env.args = vec!["jruby".into(), "-Xprofile=nope".into()];
+        let error = LaunchOptions::default().parse(&env).unwrap_err();
+        assert!(error.to_string().contains("unknown profile 'nope' (known profiles: dev, server, manage, headless)"), "{}", error);
+    }
+
+    #[test]
+    fn test_options_after_script() {
+        let dir = temp_dir("options_after_script");
+        fs::write(dir.join("data"), "-J-Xmx9g\n").unwrap();
+
+        let mut env = empty_env();
+        env.current_dir = Some(dir.to_path_buf());
+        env.args = ["jruby", "script.rb", "-J-Xmx1g", "--heap=2g", "@data", "--ng-sever"].iter().map(OsString::from).collect();
+
+        let mut options = LaunchOptions::default();
+        options.config.merge(&PathBuf::from("/etc/jruby/launcher.toml"), "[aliases]\n\"--heap\" = [\"-J-Xmx{}\"]\n").unwrap();
+        let mistyped = options.parse_arguments(&env, env.args[1..].iter().map(|arg| (arg.clone(), Origin::CommandLine)).collect());
+
+        assert!(mistyped.unwrap().is_empty());
+        assert_eq!(options.java_args, ["-Xmx1g", "-Xmx2g"]);
+        assert_eq!(options.program_args, ["script.rb", "@data", "--ng-sever"]);
+    }
+
+    #[test]
+    fn test_mistyped_options() {
+        let mut env = empty_env();
+        env.args = ["jruby", "-Xjdkhom", "/opt/jdk", "-e", "1"].iter().map(OsString::from).collect();
+
+        let mut options = LaunchOptions::default();
+        let error = options.parse(&env).unwrap_err();
+
+        assert!(error.to_string().contains("unknown option -Xjdkhom (did you mean -Xjdkhome?)"), "{}", error);
+        assert!(options.java_args.is_empty());
+        assert!(options.program_args.is_empty());
+
+        env.args = ["jruby", "--ng-sever", "-Xcompile.mode=OFF", "-e", "1"].iter().map(OsString::from).collect();
+        let mut options = LaunchOptions::default();
+        options.parse(&env).unwrap();
+
+        assert_eq!(options.java_args, ["-Djruby.compile.mode=OFF"]);
+        assert_eq!(options.program_args, ["--ng-sever", "-e", "1"]);
+    }
+
+    #[test]
+    fn test_aliases() {
+        let mut env = empty_env();
+        env.args = ["jruby", "--trace-sql", "--heap=4g", "app.rb", "--heap=1g"].iter().map(OsString::from).collect();
+
+        let mut options = LaunchOptions::default();
+        options.config.merge(&PathBuf::from("/etc/jruby/launcher.toml"), r#"
+            [aliases]
+            "--heap" = ["-J-Xmx{}"]
+            "--trace-sql" = ["-J-Dsql.trace=true", "-Xcp:a", "sqltrace.jar", "--trace-sql"]
+        "#).unwrap();
+        let mut bad = LaunchOptions { config: options.config.clone(), ..Default::default() };
+        options.parse(&env).unwrap();
+
+        assert_eq!(options.java_args, ["-Dsql.trace=true", "-Xmx4g", "-Xmx1g"]);
+        assert_eq!(options.classpath_after, vec![PathBuf::from("sqltrace.jar")]);
+        assert_eq!(options.program_args, ["--trace-sql", "app.rb"]);
+
+        env.args = vec!["jruby".into(), "--heap".into()];
+        let error = bad.parse(&env).unwrap_err();
+        assert!(error.to_string().contains("--heap needs a value"), "{}", error);
+    }
+}
@@ -0,0 +1,556 @@
+use std::error::Error;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use log::info;
+use toml::Value;
+use crate::environment::Environment;
+use crate::launch_options::{LaunchError, DEV_MODE_JAVA_OPTIONS};
+use crate::option_table;
+
+pub const CONFIG_FILE: &str = "launcher.toml";
+pub const PROJECT_CONFIG_FILE: &str = ".jruby-launcher.toml";
+
+/// Launcher settings from launcher.toml files.
+///
+/// Files are read in this order and later ones win:
+///  1. /etc/jruby/launcher.toml (not on windows)
+///  2. $JRUBY_HOME/etc/launcher.toml
+///  3. $XDG_CONFIG_HOME/jruby/launcher.toml (~/.config/jruby/launcher.toml)
+///  4. the nearest .jruby-launcher.toml at or above the current directory
+///
+/// A later file replaces single values (java_version, jdk_home, cache, log_cache, trace)
+/// and adds to lists (java_options, classpath_prepend, classpath_append).  Environment
+/// variables and then the command line override all of them.  Relative paths are relative
+/// to the file they are in.
+///
+/// `[profiles.<name>]` tables define profiles for -Xprofile=<name>.  A later file defining a
+/// profile with the same name replaces it whole, including the built-in ones.  `[aliases]`
+/// define new options in the same way.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LauncherConfig {
+    pub files: Vec<PathBuf>, // which files we read
+    pub java_options: Vec<String>,
+    pub java_version: Option<(String, PathBuf)>, // and the file it came from
+    pub jdk_home: Option<PathBuf>,
+    pub classpath_prepend: Vec<PathBuf>,
+    pub classpath_append: Vec<PathBuf>,
+    pub cache: Option<bool>,
+    pub log_cache: Option<bool>,
+    pub trace: Option<PathBuf>,
+    pub profiles: Vec<(String, Profile)>,
+    pub aliases: Vec<(String, Alias)>,
+}
+
+/// A user defined option which stands for other options.  A `{}` in the expansion makes it
+/// take a value: with `"--heap" = ["-J-Xmx{}"]` then `--heap=4g` is `-J-Xmx4g`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Alias {
+    pub expansion: Vec<String>,
+    pub description: Option<String>,
+}
+
+impl Alias {
+    pub fn takes_value(&self) -> bool {
+        self.expansion.iter().any(|arg| arg.contains("{}"))
+    }
+
+    pub fn expand(&self, name: &str, value: Option<&str>) -> Result<Vec<String>, String> {
+        match value {
+            None if self.takes_value() => Err(format!("{} needs a value: {}=<value>", name, name)),
+            Some(_) if !self.takes_value() => Err(format!("{} does not take a value", name)),
+            _ => Ok(self.expansion.iter().map(|arg| arg.replace("{}", value.unwrap_or(""))).collect()),
+        }
+    }
+}
+
+/// A named bundle of settings applied by -Xprofile=<name> (or --dev and friends).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Profile {
+    pub origin: String, // "built-in" or the file which defined it
+    pub description: Option<String>,
+    pub include: Vec<String>, // other profiles applied first
+    pub java_options: Vec<String>,
+    pub properties: Vec<(String, String)>, // JRuby properties (-Djruby.<name>=<value>)
+    pub classpath_prepend: Vec<PathBuf>,
+    pub classpath_append: Vec<PathBuf>,
+    pub cache: Option<bool>,
+    pub log_cache: Option<bool>,
+}
+
+impl Profile {
+    fn builtin(description: &str, java_options: &[&str]) -> Profile {
+        Profile {
+            origin: "built-in".to_string(),
+            description: Some(description.to_string()),
+            java_options: java_options.iter().map(|option| option.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    /// What -Xprofiles shows for this profile.
+    pub fn describe(&self, name: &str) -> String {
+        let mut description = format!("{} ({})", name, self.origin);
+        if let Some(text) = &self.description {
+            description.push_str(&format!(": {}", text));
+        }
+        description.push('\n');
+
+        let paths = |paths: &Vec<PathBuf>| paths.iter().map(|p| p.display().to_string()).collect::<Vec<_>>();
+        let properties: Vec<String> = self.properties.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+        let lists = [
+            ("include", self.include.clone()),
+            ("java_options", self.java_options.clone()),
+            ("properties", properties),
+            ("classpath_prepend", paths(&self.classpath_prepend)),
+            ("classpath_append", paths(&self.classpath_append)),
+        ];
+
+        for (key, values) in lists.iter().filter(|(_, values)| !values.is_empty()) {
+            description.push_str(&format!("    {} = {}\n", key, values.join(" ")));
+        }
+        for (key, value) in [("cache", self.cache), ("log_cache", self.log_cache)].iter() {
+            if let Some(value) = value {
+                description.push_str(&format!("    {} = {}\n", key, value));
+            }
+        }
+
+        description
+    }
+}
+
+/// Profiles behind --dev, --server, --manage and --headless.
+pub fn builtin_profiles() -> Vec<(String, Profile)> {
+    let manage = Profile {
+        properties: vec![("management.enabled".to_string(), "true".to_string())],
+        ..Profile::builtin("enable JMX management", &["-Dcom.sun.management.jmxremote"])
+    };
+
+    vec![
+        ("dev".to_string(), Profile::builtin("tune the JVM for fast startup of short lived development runs", &DEV_MODE_JAVA_OPTIONS)),
+        ("server".to_string(), Profile::builtin("use the server VM", &["-server"])),
+        ("manage".to_string(), manage),
+        ("headless".to_string(), Profile::builtin("run without a display (java.awt.headless)", &["-Djava.awt.headless=true"])),
+    ]
+}
+
+impl LauncherConfig {
+    pub(crate) fn load(env: &Environment, jruby_home: Option<&Path>) -> Result<LauncherConfig, Box<dyn Error>> {
+        let mut config = LauncherConfig::default();
+
+        for file in LauncherConfig::layers(env, jruby_home) {
+            let contents = match fs::read_to_string(&file) {
+                Ok(contents) => contents,
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(Box::new(LaunchError { message: format!("{}: {}", file.display(), e) })),
+            };
+
+            config.merge(&file, &contents).map_err(|message| LaunchError { message })?;
+        }
+
+        config.check_profiles().map_err(|message| LaunchError { message })?;
+
+        Ok(config)
+    }
+
+    /// The profile called `name` with config files overriding built-in ones.
+    pub fn profile(&self, name: &str) -> Option<Profile> {
+        self.profiles.iter().rev()
+            .find(|(profile_name, _)| profile_name == name)
+            .map(|(_, profile)| profile.clone())
+            .or_else(|| builtin_profiles().into_iter().find(|(n, _)| n == name).map(|(_, profile)| profile))
+    }
+
+    pub fn alias(&self, name: &str) -> Option<&Alias> {
+        self.aliases.iter().rev().find(|(alias_name, _)| alias_name == name).map(|(_, alias)| alias)
+    }
+
+    /// Every alias once, in the order first defined but as the last layer defines it.
+    pub fn alias_list(&self) -> Vec<(String, Alias)> {
+        let mut aliases: Vec<(String, Alias)> = vec![];
+
+        for (name, alias) in self.aliases.iter() {
+            match aliases.iter_mut().find(|(n, _)| n == name) {
+                Some((_, existing)) => *existing = alias.clone(),
+                None => aliases.push((name.clone(), alias.clone())),
+            }
+        }
+
+        aliases
+    }
+
+    /// All profile names, built-in first.
+    pub fn profile_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = builtin_profiles().into_iter().map(|(name, _)| name).collect();
+
+        for (name, _) in self.profiles.iter() {
+            if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+
+        names
+    }
+
+    // Every include has to exist and profiles cannot include themselves (even indirectly).
+    fn check_profiles(&self) -> Result<(), String> {
+        fn visit(config: &LauncherConfig, name: &str, stack: &mut Vec<String>) -> Result<(), String> {
+            if stack.iter().any(|n| n == name) {
+                stack.push(name.to_string());
+                return Err(format!("profile '{}' includes itself ({})", name, stack.join(" -> ")));
+            }
+
+            let profile = config.profile(name).ok_or_else(|| format!("profile '{}' includes unknown profile '{}'",
+                                                                   stack.last().map(|s| s.as_str()).unwrap_or(""), name))?;
+            stack.push(name.to_string());
+            for include in profile.include.iter() {
+                visit(config, include, stack)?;
+            }
+            stack.pop();
+
+            Ok(())
+        }
+
+        for (name, _) in self.profiles.iter() {
+            visit(self, name, &mut vec![])?;
+        }
+
+        Ok(())
+    }
+
+    /// Every file we would read, least important first, whether it exists or not.
+    pub(crate) fn layers(env: &Environment, jruby_home: Option<&Path>) -> Vec<PathBuf> {
+        let mut layers = vec![];
+
+        if cfg!(unix) {
+            layers.push(PathBuf::from("/etc/jruby").join(CONFIG_FILE));
+        }
+
+        if let Some(jruby_home) = jruby_home {
+            layers.push(jruby_home.join("etc").join(CONFIG_FILE));
+        }
+
+        if let Some(config_dir) = user_config_dir(env) {
+            layers.push(config_dir.join(CONFIG_FILE));
+        }
+
+        let project = env.current_dir.as_ref().and_then(|dir| dir
+            .ancestors()
+            .map(|dir| dir.join(PROJECT_CONFIG_FILE))
+            .find(|file| file.exists()));
+        if let Some(project) = project {
+            layers.push(project);
+        }
+
+        layers
+    }
+
+    pub(crate) fn merge(&mut self, file: &Path, contents: &str) -> Result<(), String> {
+        let table = match contents.parse::<Value>() {
+            Ok(Value::Table(table)) => table,
+            Ok(_) => return Err(format!("{}: expected a table", file.display())),
+            Err(e) => return Err(format!("{}: {}", file.display(), e)),
+        };
+        let dir = file.parent().unwrap_or_else(|| Path::new(""));
+        let setting = Setting { file, dir };
+
+        for (key, value) in table.iter() {
+            match key.as_str() {
+                "java_options" => self.java_options.extend(setting.strings(key, value)?),
+                "java_version" => self.java_version = Some((setting.string(key, value)?, file.to_path_buf())),
+                "jdk_home" => self.jdk_home = Some(setting.path(key, value)?),
+                "classpath_prepend" => self.classpath_prepend.extend(setting.paths(key, value)?),
+                "classpath_append" => self.classpath_append.extend(setting.paths(key, value)?),
+                "cache" => self.cache = Some(setting.boolean(key, value)?),
+                "log_cache" => self.log_cache = Some(setting.boolean(key, value)?),
+                "trace" => self.trace = Some(match setting.string(key, value)?.as_str() {
+                    "__stdout__" => PathBuf::from("__stdout__"),
+                    _ => setting.path(key, value)?,
+                }),
+                "profiles" => {
+                    let profiles = value.as_table().ok_or_else(|| setting.error(key, "a table of profiles"))?;
+
+                    for (name, profile) in profiles.iter() {
+                        let profile = setting.profile(name, profile)?;
+                        self.profiles.push((name.clone(), profile));
+                    }
+                }
+                "aliases" => {
+                    let aliases = value.as_table().ok_or_else(|| setting.error(key, "a table of aliases"))?;
+
+                    for (name, alias) in aliases.iter() {
+                        let alias = setting.alias(name, alias)?;
+                        self.aliases.push((name.clone(), alias));
+                    }
+                }
+                _ => return Err(format!("{}: unknown setting '{}'", file.display(), key)),
+            }
+        }
+
+        info!("Read launcher config {:?}", file);
+        self.files.push(file.to_path_buf());
+        Ok(())
+    }
+}
+
+// Typed access to values of one config file with errors which say where the problem is.
+struct Setting<'a> {
+    file: &'a Path,
+    dir: &'a Path,
+}
+
+impl Setting<'_> {
+    fn error(&self, key: &str, expected: &str) -> String {
+        format!("{}: '{}' should be {}", self.file.display(), key, expected)
+    }
+
+    fn string(&self, key: &str, value: &Value) -> Result<String, String> {
+        value.as_str().map(|s| s.to_string()).ok_or_else(|| self.error(key, "a string"))
+    }
+
+    fn strings(&self, key: &str, value: &Value) -> Result<Vec<String>, String> {
+        value.as_array()
+            .and_then(|values| values.iter().map(|v| v.as_str().map(|s| s.to_string())).collect())
+            .ok_or_else(|| self.error(key, "a list of strings"))
+    }
+
+    fn boolean(&self, key: &str, value: &Value) -> Result<bool, String> {
+        value.as_bool().ok_or_else(|| self.error(key, "true or false"))
+    }
+
+    fn path(&self, key: &str, value: &Value) -> Result<PathBuf, String> {
+        Ok(self.dir.join(self.string(key, value)?))
+    }
+
+    fn paths(&self, key: &str, value: &Value) -> Result<Vec<PathBuf>, String> {
+        Ok(self.strings(key, value)?.iter().map(|path| self.dir.join(path)).collect())
+    }
+
+    // Property values can be any TOML scalar (compile.mode = "OFF", debug.fullTrace = true).
+    fn properties(&self, key: &str, value: &Value) -> Result<Vec<(String, String)>, String> {
+        let table = value.as_table().ok_or_else(|| self.error(key, "a table of properties"))?;
+
+        table.iter()
+            .map(|(name, value)| match value {
+                Value::String(value) => Ok((name.clone(), value.clone())),
+                Value::Array(_) | Value::Table(_) => Err(self.error(&format!("{}.{}", key, name), "a string, number or boolean")),
+                value => Ok((name.clone(), value.to_string())),
+            })
+            .collect()
+    }
+
+    // Either just the expansion or a table with expansion and description.
+    fn alias(&self, name: &str, value: &Value) -> Result<Alias, String> {
+        let key = format!("aliases.{}", name);
+
+        if !name.starts_with('-') || option_table::lookup(name).is_some() {
+            return Err(format!("{}: alias '{}' must start with - and not be a launcher option", self.file.display(), name));
+        }
+
+        match value {
+            Value::Array(_) => Ok(Alias { expansion: self.strings(&key, value)?, description: None }),
+            Value::Table(table) => {
+                let expansion = table.get("expansion").ok_or_else(|| self.error(&key, "a table with an expansion"))?;
+                let description = match table.get("description") {
+                    Some(description) => Some(self.string(&format!("{}.description", key), description)?),
+                    None => None,
+                };
+
+                Ok(Alias { expansion: self.strings(&format!("{}.expansion", key), expansion)?, description })
+            }
+            _ => Err(self.error(&key, "a list of options or a table with expansion and description")),
+        }
+    }
+
+    fn profile(&self, name: &str, value: &Value) -> Result<Profile, String> {
+        let table = value.as_table().ok_or_else(|| self.error(&format!("profiles.{}", name), "a table"))?;
+        let mut profile = Profile { origin: self.file.display().to_string(), ..Default::default() };
+
+        for (key, value) in table.iter() {
+            let qualified = format!("profiles.{}.{}", name, key);
+            let key = qualified.as_str();
+
+            match qualified.rsplit('.').next().unwrap() {
+                "description" => profile.description = Some(self.string(key, value)?),
+                "include" => profile.include = self.strings(key, value)?,
+                "java_options" => profile.java_options = self.strings(key, value)?,
+                "properties" => profile.properties = self.properties(key, value)?,
+                "classpath_prepend" => profile.classpath_prepend = self.paths(key, value)?,
+                "classpath_append" => profile.classpath_append = self.paths(key, value)?,
+                "cache" => profile.cache = Some(self.boolean(key, value)?),
+                "log_cache" => profile.log_cache = Some(self.boolean(key, value)?),
+                _ => return Err(format!("{}: unknown setting '{}'", self.file.display(), key)),
+            }
+        }
+
+        Ok(profile)
+    }
+}
+
+/// The user's jruby config directory: $XDG_CONFIG_HOME/jruby or ~/.config/jruby.
+pub(crate) fn user_config_dir(env: &Environment) -> Option<PathBuf> {
+    let config_home = match (&env.xdg_config_home, &env.home) {
+        (Some(config_home), _) => PathBuf::from(config_home),
+        (None, Some(home)) => PathBuf::from(home).join(".config"),
+        (None, None) => return None,
+    };
+
+    Some(config_home.join("jruby"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use crate::file_helper::tests::temp_dir;
+    use crate::environment::tests::empty_env;
+    use crate::launcher_config::{Alias, LauncherConfig, Profile};
+
+    #[test]
+    fn test_merge_layers() {
+        let mut config = LauncherConfig::default();
+
+        config.merge(Path::new("/opt/jruby/etc/launcher.toml"), r#"
+            java_options = ["-Xmx1g"]
+            java_version = ">=17"
+            classpath_append = ["lib/extra.jar"]
+            cache = false
+        "#).unwrap();
+        config.merge(Path::new("/work/app/.jruby-launcher.toml"), r#"
+            java_options = ["-Xmx2g"]
+            java_version = "21"
+            jdk_home = "/opt/jdk-21"
+            cache = true
+            log_cache = true
+            trace = "__stdout__"
+        "#).unwrap();
+
+        assert_eq!(config.java_options, vec!["-Xmx1g", "-Xmx2g"]);
+        assert_eq!(config.java_version, Some(("21".to_string(), PathBuf::from("/work/app/.jruby-launcher.toml"))));
+        assert_eq!(config.jdk_home, Some(PathBuf::from("/opt/jdk-21")));
+        assert_eq!(config.classpath_append, vec![PathBuf::from("/opt/jruby/etc/lib/extra.jar")]);
+        assert_eq!(config.cache, Some(true));
+        assert_eq!(config.log_cache, Some(true));
+        assert_eq!(config.trace, Some(PathBuf::from("__stdout__")));
+        assert_eq!(config.files.len(), 2);
+    }
+
+    #[test]
+    fn test_merge_errors() {
+        let file = Path::new("/etc/jruby/launcher.toml");
+
+        assert!(LauncherConfig::default().merge(file, "java_options = \"-Xmx1g\"").unwrap_err()
+            .contains("'java_options' should be a list of strings"));
+        assert!(LauncherConfig::default().merge(file, "jvm_options = []").unwrap_err()
+            .contains("unknown setting 'jvm_options'"));
+        assert!(LauncherConfig::default().merge(file, "cache = ").unwrap_err()
+            .starts_with("/etc/jruby/launcher.toml: "));
+    }
+
+    #[test]
+    fn test_layers() {
+        let root = temp_dir("launcher_config");
+        let project = root.join("project");
+        fs::create_dir_all(project.join("src")).unwrap();
+        fs::write(project.join(".jruby-launcher.toml"), "java_options = [\"-Dproject=true\"]\n").unwrap();
+        fs::create_dir_all(root.join("jruby").join("etc")).unwrap();
+        fs::write(root.join("jruby").join("etc").join("launcher.toml"), "java_options = [\"-Dhome=true\"]\n").unwrap();
+
+        let mut env = empty_env();
+        env.home = Some(root.join("user").into_os_string());
+        env.current_dir = Some(project.join("src"));
+
+        let layers = LauncherConfig::layers(&env, Some(&root.join("jruby")));
+        let config = LauncherConfig::load(&env, Some(&root.join("jruby")));
+
+        assert!(layers.ends_with(&[root.join("jruby").join("etc").join("launcher.toml"),
+                                   root.join("user").join(".config").join("jruby").join("launcher.toml"),
+                                   project.join(".jruby-launcher.toml")]));
+        assert!(config.unwrap().java_options.ends_with(&["-Dhome=true".to_string(), "-Dproject=true".to_string()]));
+
+        // One which exists but cannot be read is not the same as a missing one.
+        fs::create_dir_all(root.join("user").join(".config").join("jruby").join("launcher.toml")).unwrap();
+        let unreadable = LauncherConfig::load(&env, Some(&root.join("jruby")));
+        assert!(unreadable.unwrap_err().to_string().contains("launcher.toml: "));
+    }
+
+    #[test]
+    fn test_profiles() {
+        let mut config = LauncherConfig::default();
+
+        config.merge(Path::new("/etc/jruby/launcher.toml"), r#"
+            [profiles.debug]
+            description = "full backtraces"
+            java_options = ["-Xdebug"]
+            properties = { "debug.fullTrace" = true, "backtrace.style" = "raw" }
+
+            [profiles.ci]
+            include = ["dev", "debug"]
+            cache = false
+        "#).unwrap();
+        config.merge(Path::new("/home/u/.config/jruby/launcher.toml"), r#"
+            [profiles.dev]
+            java_options = ["-XX:TieredStopAtLevel=1"]
+        "#).unwrap();
+        config.check_profiles().unwrap();
+
+        let debug = config.profile("debug").unwrap();
+        assert_eq!(debug.properties, vec![("backtrace.style".to_string(), "raw".to_string()),
+                                          ("debug.fullTrace".to_string(), "true".to_string())]);
+        assert_eq!(config.profile("ci").unwrap().include, vec!["dev", "debug"]);
+        // user's dev replaces the built-in one
+        assert_eq!(config.profile("dev").unwrap().java_options, vec!["-XX:TieredStopAtLevel=1"]);
+        assert_eq!(config.profile("server").unwrap().origin, "built-in");
+        assert!(config.profile("nope").is_none());
+        assert_eq!(config.profile_names(), vec!["dev", "server", "manage", "headless", "ci", "debug"]);
+        assert!(debug.describe("debug").contains("debug (/etc/jruby/launcher.toml): full backtraces\n    java_options = -Xdebug\n"));
+    }
+
+    #[test]
+    fn test_profile_errors() {
+        let file = Path::new("/etc/jruby/launcher.toml");
+        let mut looped = LauncherConfig::default();
+        looped.merge(file, "[profiles.a]\ninclude = [\"b\"]\n[profiles.b]\ninclude = [\"a\"]\n").unwrap();
+        let mut missing = LauncherConfig::default();
+        missing.merge(file, "[profiles.a]\ninclude = [\"server\", \"nope\"]\n").unwrap();
+
+        assert_eq!(looped.check_profiles().unwrap_err(), "profile 'a' includes itself (a -> b -> a)");
+        assert_eq!(missing.check_profiles().unwrap_err(), "profile 'a' includes unknown profile 'nope'");
+        assert!(LauncherConfig::default().merge(file, "[profiles.a]\njava_option = []\n").unwrap_err()
+            .contains("unknown setting 'profiles.a.java_option'"));
+        assert_eq!(Profile::default().describe("empty"), "empty ()\n");
+    }
+
+    #[test]
+    fn test_aliases() {
+        let file = Path::new("/etc/jruby/launcher.toml");
+        let mut config = LauncherConfig::default();
+
+        config.merge(file, r#"
+            [aliases]
+            "--heap" = ["-J-Xmx{}"]
+            "--trace-sql" = { expansion = ["-J-Dsql.trace=true", "-Xcp:a", "sqltrace.jar"], description = "log every query" }
+        "#).unwrap();
+
+        let heap = config.alias("--heap").unwrap();
+        assert!(heap.takes_value());
+        assert_eq!(heap.expand("--heap", Some("4g")).unwrap(), vec!["-J-Xmx4g"]);
+        assert_eq!(heap.expand("--heap", None).unwrap_err(), "--heap needs a value: --heap=<value>");
+
+        let trace_sql = config.alias("--trace-sql").unwrap();
+        assert_eq!(trace_sql.description.as_deref(), Some("log every query"));
+        assert_eq!(trace_sql.expand("--trace-sql", None).unwrap().len(), 3);
+        assert!(trace_sql.expand("--trace-sql", Some("x")).is_err());
+        assert!(config.alias("--nope").is_none());
+
+        config.merge(Path::new("/home/user/.config/jruby/launcher.toml"), "[aliases]\n\"--heap\" = [\"-J-Xms{}\"]\n").unwrap();
+        let names: Vec<_> = config.alias_list().into_iter().map(|(name, _)| name).collect();
+        assert_eq!(names, vec!["--heap", "--trace-sql"]);
+        assert_eq!(config.alias_list()[0].1.expansion, vec!["-J-Xms{}"]);
+
+        assert!(LauncherConfig::default().merge(file, "[aliases]\n\"--dev\" = []\n").unwrap_err().contains("not be a launcher option"));
+        assert!(LauncherConfig::default().merge(file, "[aliases]\n\"fast\" = []\n").unwrap_err().contains("must start with -"));
+        assert!(LauncherConfig::default().merge(file, "[aliases]\n\"--x\" = 1\n").is_err());
+        assert_eq!(Alias::default().expand("--empty", None).unwrap(), Vec::<String>::new());
+    }
+}
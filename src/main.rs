@@ -1,20 +1,30 @@
 extern crate log;
 extern crate sys_info;
 
+pub mod argfile;
 pub mod environment;
 pub mod file_helper;
 pub mod file_logger;
+pub mod java_compatibility;
+pub mod java_installation;
+pub mod java_version_file;
+pub mod jdk_discovery;
+pub mod jruby_installation;
 pub mod launch_options;
+pub mod launcher_config;
+pub mod module_opts;
 #[cfg(windows)] pub mod win_launch;
+pub mod option_fragments;
+pub mod option_table;
 pub mod os_string_ext;
+pub mod probe_cache;
+pub mod version_constraint;
 
 use std::env;
 use std::error::Error;
 use std::io::{stderr, Write};
 use std::ffi::OsString;
 
-//const IS_SIXTY_FOUR: bool = cfg!(target_pointer_width = "64");
-
 fn print_error(err: Box<dyn Error>) {
     let mut err = err.as_ref();
     let _ = writeln!(stderr(), "error: {}", err);
@@ -61,12 +71,34 @@ fn main() {
     }
 
     let mut options = options.unwrap();
+    if options.print_profiles {
+        print!("{}", options.profiles_report());
+        return;
+    }
+
+    if let Some(shell) = &options.print_completion {
+        print!("{}", option_table::completion(shell));
+        return;
+    }
+
+    if options.print_version {
+        print!("{}", options.version_report());
+        return;
+    }
+
+    if options.print_help {
+        print!("{}", option_table::help(&options.config.alias_list()));
+    }
+
     if options.nailgun_client {
         options.program_args.insert(0, OsString::from("org.jruby.util.NailMain"));
     }
 
+    // JRUBY_OPTS is already on the command line and JRuby would read it a second time.
+    env::remove_var("JRUBY_OPTS");
+
     if options.command_only {
-        println!("{:?}", options.program_args);
+        println!("{}", options.command_report().to_string_lossy());
     } else {
         execute(options.java_location.clone().unwrap().into_os_string(), options.command_line());
     }
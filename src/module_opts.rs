@@ -0,0 +1,229 @@
+use std::ffi::OsString;
+use std::path::Path;
+use log::{info, warn};
+use crate::argfile::read_os_string;
+use crate::java_installation::JavaInstallation;
+
+pub const MODULE_OPTS_FILE: &str = ".jruby.module_opts";
+
+/// What we use when a JRuby install has no bin/.jruby.module_opts.
+pub const DEFAULT_MODULE_OPTIONS: [&str; 8] = [
+    "--add-opens", "java.base/java.io=org.jruby.dist",
+    "--add-opens", "java.base/java.nio.channels=org.jruby.dist",
+    "--add-opens", "java.base/sun.nio.ch=org.jruby.dist",
+    "--add-opens", "java.management/sun.management=org.jruby.dist",
+];
+
+/// java options which take a value naming modules, as `--opt value` or `--opt=value`.
+const VALUED_OPTIONS: [&str; 5] = ["--add-opens", "--add-exports", "--add-reads", "--add-modules", "--enable-native-access"];
+
+/// One option from a module options file.  Anything which is not one of VALUED_OPTIONS has
+/// no value and is passed along as is.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModuleOption {
+    pub name: String,
+    pub value: Option<String>,
+}
+
+impl ModuleOption {
+    /// The JDK module this opens, exports or reads from (`java.base` in `java.base/java.io=...`).
+    /// Other modules (like JRuby's own org.jruby.dist) are never in a JDK's release MODULES.
+    pub fn jdk_module(&self) -> Option<&str> {
+        let module = match (self.name.as_str(), &self.value) {
+            ("--add-opens", Some(value)) | ("--add-exports", Some(value)) => value.split('/').next(),
+            ("--add-reads", Some(value)) => value.split('=').next(),
+            _ => None,
+        };
+
+        module.filter(|module| module.starts_with("java.") || module.starts_with("jdk."))
+    }
+
+    fn words(&self) -> Vec<OsString> {
+        let mut words = vec![OsString::from(&self.name)];
+
+        if let Some(value) = &self.value {
+            words.push(OsString::from(value));
+        }
+        words
+    }
+}
+
+/// Read a module options file.  java reads it as an @argfile so we split it the same way.
+pub(crate) fn read(file: &Path) -> Result<Vec<ModuleOption>, String> {
+    let contents = read_os_string(file)
+        .map_err(|e| format!("unable to read module options {}: {}", file.display(), e))?;
+    let words: Vec<OsString> = split_argfile(&contents.to_string_lossy()).into_iter().map(OsString::from).collect();
+
+    parse(&words).map_err(|e| format!("module options {}: {}", file.display(), e))
+}
+
+/// Split like java's @argfile: words are separated by whitespace and # starts a comment
+/// outside quotes.  Within single or double quotes a backslash escapes (\n, \t, \r, \f
+/// or the character itself) and a backslash ending the line joins the next one without its
+/// leading whitespace.  A quote still open at the end of a line ends there.
+fn split_argfile(contents: &str) -> Vec<String> {
+    let mut words = vec![];
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    let mut chars = contents.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(_), '\\') => match chars.next() {
+                Some('n') => word.get_or_insert_with(String::new).push('\n'),
+                Some('t') => word.get_or_insert_with(String::new).push('\t'),
+                Some('r') => word.get_or_insert_with(String::new).push('\r'),
+                Some('f') => word.get_or_insert_with(String::new).push('\x0c'),
+                Some(end) if end == '\r' || end == '\n' => {
+                    if end == '\r' && chars.peek() == Some(&'\n') {
+                        chars.next();
+                    }
+                    while matches!(chars.peek(), Some(' ') | Some('\t') | Some('\x0c')) {
+                        chars.next();
+                    }
+                }
+                Some(other) => word.get_or_insert_with(String::new).push(other),
+                None => {}
+            },
+            (Some(_), '\r') | (Some(_), '\n') => {
+                quote = None;
+                words.extend(word.take());
+            }
+            (Some(open), c) if c == open => quote = None,
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, ' ') | (None, '\t') | (None, '\x0c') | (None, '\r') | (None, '\n') => words.extend(word.take()),
+            (None, '#') => {
+                words.extend(word.take());
+                while !matches!(chars.peek(), None | Some('\n') | Some('\r')) {
+                    chars.next();
+                }
+            }
+            (None, '\'') | (None, '"') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+
+    words
+}
+
+pub(crate) fn parse(words: &[OsString]) -> Result<Vec<ModuleOption>, String> {
+    let mut options = vec![];
+    let mut words = words.iter().map(|word| word.to_string_lossy().into_owned());
+
+    while let Some(word) = words.next() {
+        let option = match word.split_once('=') {
+            Some((name, value)) if VALUED_OPTIONS.contains(&name) => ModuleOption { name: name.to_string(), value: Some(value.to_string()) },
+            _ if VALUED_OPTIONS.contains(&word.as_str()) => match words.next() {
+                Some(value) => ModuleOption { name: word, value: Some(value) },
+                None => return Err(format!("{} needs a value", word)),
+            },
+            _ => ModuleOption { name: word, value: None },
+        };
+        options.push(option);
+    }
+
+    Ok(options)
+}
+
+/// The java options for `module_options` minus the ones already in `java_options` (or given
+/// twice) and ones naming a JDK module `java` does not have.
+pub(crate) fn merge(module_options: Vec<ModuleOption>, java_options: &[OsString], java: &JavaInstallation) -> Vec<OsString> {
+    // A user option missing its value makes java fail anyway so there is nothing to dedupe.
+    let mut seen = parse(java_options).unwrap_or_default();
+    let mut merged = vec![];
+
+    for option in module_options {
+        if let Some(value) = option.value.as_ref().filter(|_| seen.contains(&option)) {
+            info!("Module option {} {} already given.  Skipping it.", option.name, value);
+            continue;
+        }
+
+        if let Some(module) = option.jdk_module().filter(|module| !java.modules.is_empty() && !java.has_module(module)) {
+            warn!("Java has no module {}.  Skipping {} {}", module, option.name, option.value.as_ref().unwrap());
+            continue;
+        }
+
+        merged.extend(option.words());
+        seen.push(option);
+    }
+
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsString;
+    use std::fs;
+    use crate::file_helper::tests::temp_dir;
+    use crate::java_installation::JavaInstallation;
+    use crate::module_opts::{merge, parse, read, split_argfile, ModuleOption};
+
+    fn words(words: &[&str]) -> Vec<OsString> {
+        words.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let options = parse(&words(&["--add-opens", "java.base/java.io=org.jruby.dist", "--add-reads=jdk.unsupported=ALL-UNNAMED", "-Dx=1"])).unwrap();
+
+        assert_eq!(options, vec![
+            ModuleOption { name: "--add-opens".to_string(), value: Some("java.base/java.io=org.jruby.dist".to_string()) },
+            ModuleOption { name: "--add-reads".to_string(), value: Some("jdk.unsupported=ALL-UNNAMED".to_string()) },
+            ModuleOption { name: "-Dx=1".to_string(), value: None },
+        ]);
+        assert_eq!(options[0].jdk_module(), Some("java.base"));
+        assert_eq!(options[1].jdk_module(), Some("jdk.unsupported"));
+        assert_eq!(options[2].jdk_module(), None);
+        assert_eq!(parse(&words(&["--add-exports"])).unwrap_err(), "--add-exports needs a value");
+    }
+
+    #[test]
+    fn test_read_and_merge() {
+        let dir = temp_dir("module_opts");
+        let file = dir.join(".jruby.module_opts");
+        fs::write(&file, "# opened for JRuby\n--add-opens java.base/java.io=org.jruby.dist\n\
+                          --add-opens 'java.base/sun.nio.ch=org.jruby.dist'  # selectors\n\
+                          --add-opens java.management/sun.management=org.jruby.dist\n\
+                          --add-opens=java.base/java.io=org.jruby.dist\n").unwrap();
+        fs::write(dir.join("release"), "JAVA_VERSION=\"21.0.2\"\nMODULES=\"java.base\"\n").unwrap();
+
+        let options = read(&file);
+        let java = JavaInstallation::from_home(&dir);
+
+        let merged = merge(options.unwrap(), &words(&["-Xmx1g", "--add-opens=java.base/sun.nio.ch=org.jruby.dist"]), &java);
+
+        assert_eq!(merged, words(&["--add-opens", "java.base/java.io=org.jruby.dist"]));
+    }
+
+    #[test]
+    fn test_split_argfile() {
+        assert_eq!(split_argfile("--add-opens java.base/java.io=ALL-UNNAMED # why\n-Da=1#no space\n"),
+                   vec!["--add-opens", "java.base/java.io=ALL-UNNAMED", "-Da=1"]);
+        assert_eq!(split_argfile("'-Dname=a b' \"-Dtab=\\t\" 'it\\'s'"), vec!["-Dname=a b", "-Dtab=\t", "it's"]);
+        assert_eq!(split_argfile("-Dpath=C:\\jdk '-Dpath=C:\\\\jdk'"), vec!["-Dpath=C:\\jdk", "-Dpath=C:\\jdk"]);
+        assert_eq!(split_argfile("\"java.base/\\\n    java.io=ALL-UNNAMED\""), vec!["java.base/java.io=ALL-UNNAMED"]);
+        assert_eq!(split_argfile("'open quote\n-Dnext"), vec!["open quote", "-Dnext"]);
+        assert_eq!(split_argfile("a\\\nb \"\""), vec!["a\\", "b", ""]);
+    }
+
+    #[test]
+    fn test_jdk_module_ignores_other_modules() {
+        let options = parse(&words(&["--add-reads", "org.jruby.dist=ALL-UNNAMED", "--add-exports", "org.jruby.dist/org.jruby=ALL-UNNAMED"])).unwrap();
+        let java = JavaInstallation::default();
+
+        assert_eq!(options[0].jdk_module(), None);
+        assert_eq!(options[1].jdk_module(), None);
+        assert_eq!(merge(options, &[], &java).len(), 4);
+    }
+
+    #[test]
+    fn test_merge_without_module_list() {
+        let options = parse(&words(&["--add-opens", "java.management/sun.management=org.jruby.dist"])).unwrap();
+
+        assert_eq!(merge(options, &[], &JavaInstallation::default()).len(), 2);
+    }
+}
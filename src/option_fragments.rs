@@ -0,0 +1,125 @@
+use std::collections::BTreeMap;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::path::{Path, PathBuf};
+use log::info;
+use crate::argfile::read_os_string;
+use crate::environment::Environment;
+use crate::launch_options::JavaVersion;
+use crate::launcher_config::user_config_dir;
+use crate::os_string_ext::OsStringExt;
+use crate::version_constraint::VersionConstraint;
+
+pub const FRAGMENT_DIR: &str = "launcher.d";
+pub const FRAGMENT_EXTENSION: &str = "opts";
+pub const JAVA_VERSION_DIRECTIVE: &str = "# java-version:";
+
+/// Java options dropped into a launcher.d directory.
+#[derive(Debug, Clone)]
+pub struct Fragment {
+    pub file: PathBuf,
+    pub java_version: Option<VersionConstraint>,
+    pub options: Vec<OsString>,
+}
+
+impl Fragment {
+    pub fn applies_to(&self, version: &JavaVersion) -> bool {
+        match &self.java_version {
+            Some(constraint) => constraint.matches(version),
+            None => true,
+        }
+    }
+}
+
+/// Fragment directories from lowest to highest priority: JRUBY_HOME/etc/launcher.d and
+/// then the user's launcher.d next to their launcher.toml.
+pub(crate) fn fragment_dirs(env: &Environment, jruby_home: &Path) -> Vec<PathBuf> {
+    let mut dirs = vec![jruby_home.join("etc").join(FRAGMENT_DIR)];
+
+    if let Some(config_dir) = user_config_dir(env) {
+        dirs.push(config_dir.join(FRAGMENT_DIR));
+    }
+
+    dirs
+}
+
+/// All *.opts files in `dirs` ordered by file name.  A user fragment with the same name as
+/// one in JRUBY_HOME replaces it so a distributed fragment can be turned off or changed.
+pub(crate) fn load(dirs: &[PathBuf]) -> Result<Vec<Fragment>, String> {
+    let mut files = BTreeMap::new();
+
+    for dir in dirs {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+
+        for file in entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()) {
+            if file.is_file() && file.extension() == Some(OsStr::new(FRAGMENT_EXTENSION)) {
+                files.insert(file.file_name().unwrap().to_owned(), file);
+            }
+        }
+    }
+
+    files.values().map(|file| {
+        let contents = read_os_string(file)
+            .map_err(|e| format!("unable to read option fragment {}: {}", file.display(), e))?;
+
+        parse_fragment(file, &contents).map_err(|e| format!("option fragment {}: {}", file.display(), e))
+    }).collect()
+}
+
+/// Fragments hold java options quoted like a POSIX shell with # comments.  A
+/// `# java-version: <constraint>` line limits it to the Java versions matching constraint.
+fn parse_fragment(file: &Path, contents: &OsString) -> Result<Fragment, String> {
+    let mut java_version = None;
+
+    for line in contents.to_string_lossy().lines() {
+        if let Some(constraint) = line.trim().strip_prefix(JAVA_VERSION_DIRECTIVE) {
+            java_version = Some(VersionConstraint::parse(constraint)
+                .ok_or_else(|| format!("invalid java-version '{}'", constraint.trim()))?);
+        }
+    }
+
+    if let Some(constraint) = &java_version {
+        info!("Option fragment {:?} is for Java {}", file, constraint);
+    }
+
+    Ok(Fragment { file: file.to_path_buf(), java_version, options: contents.shell_split_commented()? })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsString;
+    use std::fs;
+    use crate::file_helper::tests::temp_dir;
+    use crate::launch_options::JavaVersion;
+    use crate::option_fragments::load;
+
+    #[test]
+    fn test_load() {
+        let dir = temp_dir("option_fragments");
+        let (system, user) = (dir.join("system"), dir.join("user"));
+        fs::create_dir_all(&system).unwrap();
+        fs::create_dir_all(&user).unwrap();
+        fs::write(system.join("20-agent.opts"), "-javaagent:agent.jar\n").unwrap();
+        fs::write(system.join("10-opens.opts"), "# java-version: >=17\n--add-opens 'java.base/java.io=ALL-UNNAMED'\n").unwrap();
+        fs::write(system.join("30-gc.opts"), "-XX:+UseG1GC\n").unwrap();
+        fs::write(system.join("README"), "not options\n").unwrap();
+        fs::write(user.join("30-gc.opts"), "-XX:+UseZGC  # replaces the system one\n").unwrap();
+
+        let fragments = load(&[system.clone(), user.clone(), dir.join("missing")]);
+        fs::write(user.join("40-bad.opts"), "# java-version: nope\n").unwrap();
+        let bad = load(&[system, user]);
+
+        let fragments = fragments.unwrap();
+        let names: Vec<_> = fragments.iter().map(|f| f.file.file_name().unwrap().to_string_lossy().into_owned()).collect();
+        assert_eq!(names, vec!["10-opens.opts", "20-agent.opts", "30-gc.opts"]);
+        assert_eq!(fragments[0].options, vec![OsString::from("--add-opens"), OsString::from("java.base/java.io=ALL-UNNAMED")]);
+        assert!(fragments[0].applies_to(&JavaVersion::parse("21").unwrap()));
+        assert!(!fragments[0].applies_to(&JavaVersion::parse("11.0.2").unwrap()));
+        assert!(fragments[1].applies_to(&JavaVersion::parse("1.8").unwrap()));
+        assert_eq!(fragments[2].options, vec![OsString::from("-XX:+UseZGC")]);
+        assert!(bad.unwrap_err().contains("invalid java-version 'nope'"));
+    }
+}
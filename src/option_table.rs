@@ -0,0 +1,537 @@
+use std::ffi::OsString;
+use std::path::PathBuf;
+use crate::environment::ENVIRONMENT_VARIABLES;
+use crate::launch_options::LaunchOptions;
+use crate::launcher_config::Alias;
+
+/// How an option takes its argument (if it takes one at all).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Arity {
+    Flag,
+    Separate, // -Xtrace <file>
+    Attached, // -J-Xmx2g
+}
+
+/// Who ends up consuming an option.  Launcher options never reach java or JRuby.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scope {
+    Launcher,
+    Java,
+    JRuby,
+}
+
+/// Applies an option to what we are building.  Flags get an empty value.
+pub type Handler = fn(&mut LaunchOptions, OsString);
+
+/// An option the launcher itself understands.
+pub struct LauncherOption {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub arity: Arity,
+    pub argument: &'static str,
+    pub values: &'static [&'static str], // allowed values (empty means anything)
+    pub scope: Scope,
+    pub apply: Handler,
+    pub help: &'static str,
+}
+
+pub const COMPLETION_SHELLS: [&str; 3] = ["bash", "zsh", "fish"];
+
+pub const LAUNCHER_OPTIONS: [LauncherOption; 33] = [
+    LauncherOption {
+        name: "-Xhelp", aliases: &["-X"], arity: Arity::Flag, argument: "", values: &[], scope: Scope::JRuby,
+        apply: |options, _| {
+            options.print_help = true;
+            options.java_args.push(OsString::from("-Djruby.launcher.nopreamble=true"));
+            options.program_args.push(OsString::from("-X"));
+        },
+        help: "print this help and JRuby's -X help",
+    },
+    LauncherOption {
+        name: "-Xversion", aliases: &[], arity: Arity::Flag, argument: "", values: &[], scope: Scope::Launcher,
+        apply: |options, _| options.print_version = true,
+        help: "print launcher, JRuby and Java versions then exit",
+    },
+    LauncherOption {
+        name: "-Xcompletion", aliases: &[], arity: Arity::Separate, argument: "<shell>", values: &COMPLETION_SHELLS, scope: Scope::Launcher,
+        apply: |options, shell| options.print_completion = Some(shell.to_string_lossy().into_owned()),
+        help: "print a completion script for <shell> then exit",
+    },
+    LauncherOption {
+        name: "-Xstrict", aliases: &[], arity: Arity::Flag, argument: "", values: &[], scope: Scope::Launcher,
+        apply: |options, _| options.strict = true,
+        help: "treat mistyped launcher options as errors instead of warnings",
+    },
+    LauncherOption {
+        name: "-Xprofile=", aliases: &[], arity: Arity::Attached, argument: "<name>", values: &[], scope: Scope::Launcher,
+        apply: |options, name| options.apply_profile(&name.to_string_lossy()),
+        help: "apply the named profile from launcher config (see -Xprofiles)",
+    },
+    LauncherOption {
+        name: "-Xprofile", aliases: &[], arity: Arity::Separate, argument: "<name>", values: &[], scope: Scope::Launcher,
+        apply: |options, name| options.apply_profile(&name.to_string_lossy()),
+        help: "same as -Xprofile=<name>",
+    },
+    LauncherOption {
+        name: "-Xprofiles", aliases: &[], arity: Arity::Flag, argument: "", values: &[], scope: Scope::Launcher,
+        apply: |options, _| options.print_profiles = true,
+        help: "print the available profiles and what they set then exit",
+    },
+    LauncherOption {
+        name: "-Xtrace", aliases: &[], arity: Arity::Separate, argument: "<file>", values: &[], scope: Scope::Launcher,
+        apply: |options, file| options.launcher_logfile = Some(PathBuf::from(file)),
+        help: "log what the launcher does to <file> (__stdout__ for the console)",
+    },
+    LauncherOption {
+        name: "-Xcommand", aliases: &[], arity: Arity::Flag, argument: "", values: &[], scope: Scope::Launcher,
+        apply: |options, _| options.command_only = true,
+        help: "print the command line instead of running it",
+    },
+    LauncherOption {
+        name: "-Xfork-java", aliases: &[], arity: Arity::Flag, argument: "", values: &[], scope: Scope::Launcher,
+        apply: |options, _| options.fork_java = true,
+        help: "run java as a child process (classpath via -cp)",
+    },
+    LauncherOption {
+        name: "-Xjdkhome", aliases: &[], arity: Arity::Separate, argument: "<dir>", values: &[], scope: Scope::Launcher,
+        apply: |options, dir| options.jdk_home = Some(PathBuf::from(dir)),
+        help: "use the JDK installed in <dir>",
+    },
+    LauncherOption {
+        name: "-Xjava-version", aliases: &[], arity: Arity::Separate, argument: "<constraint>", values: &[], scope: Scope::Launcher,
+        apply: |options, constraint| options.java_version_constraint = Some(constraint.to_string_lossy().into_owned()),
+        help: "require a Java version, e.g. '>=17,<22' or '21'",
+    },
+    LauncherOption {
+        name: "-Xbootclass", aliases: &[], arity: Arity::Separate, argument: "<class>", values: &[], scope: Scope::Launcher,
+        apply: |options, class| options.boot_class = Some(class),
+        help: "main class to launch instead of org/jruby/Main",
+    },
+    LauncherOption {
+        name: "-Xnobootclasspath", aliases: &["--no-bootclasspath"], arity: Arity::Flag, argument: "", values: &[], scope: Scope::Launcher,
+        apply: |options, _| options.no_boot_classpath = true,
+        help: "put JRuby on the classpath instead of the boot classpath",
+    },
+    LauncherOption {
+        name: "-Xcp:p", aliases: &[], arity: Arity::Separate, argument: "<path>", values: &[], scope: Scope::Launcher,
+        apply: |options, path| options.classpath_before.push(PathBuf::from(path)),
+        help: "prepend <path> to the classpath",
+    },
+    LauncherOption {
+        name: "-Xcp:a", aliases: &[], arity: Arity::Separate, argument: "<path>", values: &[], scope: Scope::Launcher,
+        apply: |options, path| options.classpath_after.push(PathBuf::from(path)),
+        help: "append <path> to the classpath",
+    },
+    LauncherOption {
+        name: "--ng", aliases: &[], arity: Arity::Flag, argument: "", values: &[], scope: Scope::Launcher,
+        apply: |options, _| options.nailgun_client = true,
+        help: "run as a nailgun client",
+    },
+    LauncherOption {
+        name: "--ng-server", aliases: &[], arity: Arity::Flag, argument: "", values: &[], scope: Scope::Launcher,
+        apply: |options, _| {
+            options.boot_class = Some(OsString::from("com/martiansoftware/nailgun/NGServer"));
+            options.java_args.push(OsString::from("-server"));
+            options.no_boot_classpath = true;
+        },
+        help: "start a nailgun server",
+    },
+    // FIXME: Implement checkpoint
+    LauncherOption {
+        name: "--cache", aliases: &[], arity: Arity::Flag, argument: "", values: &[], scope: Scope::Launcher,
+        apply: |options, _| options.regenerate_jsa_file = true,
+        help: "regenerate the AppCDS archive",
+    },
+    LauncherOption {
+        name: "--nocache", aliases: &[], arity: Arity::Flag, argument: "", values: &[], scope: Scope::Launcher,
+        apply: |options, _| options.no_cache = true,
+        help: "do not use the AppCDS archive",
+    },
+    LauncherOption {
+        name: "--rmcache", aliases: &[], arity: Arity::Flag, argument: "", values: &[], scope: Scope::Launcher,
+        apply: |options, _| options.remove_jsa_files = true,
+        help: "remove the AppCDS archive",
+    },
+    LauncherOption {
+        name: "--logcache", aliases: &[], arity: Arity::Flag, argument: "", values: &[], scope: Scope::Launcher,
+        apply: |options, _| options.log_cds = true,
+        help: "log AppCDS activity to the archive's log",
+    },
+    LauncherOption {
+        name: "-Xxss", aliases: &[], arity: Arity::Attached, argument: "<size>", values: &[], scope: Scope::Java,
+        apply: |options, size| options.xss = Some(OsString::from(format!("-Xss{}", size.to_string_lossy()))),
+        help: "java thread stack size, e.g. -Xxss4m (default 2048k)",
+    },
+    LauncherOption {
+        name: "-J-cp", aliases: &["-J-classpath"], arity: Arity::Separate, argument: "<path>", values: &[], scope: Scope::Java,
+        apply: |options, path| options.classpath_explicit.push(PathBuf::from(path)),
+        help: "use <path> as the classpath instead of CLASSPATH",
+    },
+    LauncherOption {
+        name: "-Jea", aliases: &[], arity: Arity::Flag, argument: "", values: &[], scope: Scope::Java,
+        apply: |options, _| {
+            options.java_args.push(OsString::from("-ea"));
+            options.no_boot_classpath = true;
+            eprintln!("Note: -ea option is specified, there will be no bootclasspath in order to enable assertions")
+        },
+        help: "enable java assertions (disables the boot classpath)",
+    },
+    // Must come after the -J options above which it is a prefix of.
+    LauncherOption {
+        name: "-J", aliases: &[], arity: Arity::Attached, argument: "<option>", values: &[], scope: Scope::Java,
+        apply: |options, option| options.java_args.push(option),
+        help: "pass <option> to java, e.g. -J-Xmx2g",
+    },
+    LauncherOption {
+        name: "--server", aliases: &[], arity: Arity::Flag, argument: "", values: &[], scope: Scope::Java,
+        apply: |options, _| options.apply_profile("server"),
+        help: "same as -Xprofile=server (use the server VM)",
+    },
+    LauncherOption {
+        name: "--client", aliases: &[], arity: Arity::Flag, argument: "", values: &[], scope: Scope::Java,
+        apply: |options, _| options.java_args.push(OsString::from("-client")),
+        help: "use the client VM",
+    },
+    LauncherOption {
+        name: "--dev", aliases: &[], arity: Arity::Flag, argument: "", values: &[], scope: Scope::Java,
+        apply: |options, _| options.apply_profile("dev"),
+        help: "same as -Xprofile=dev (tune the JVM for fast startup)",
+    },
+    LauncherOption {
+        name: "--sample", aliases: &[], arity: Arity::Flag, argument: "", values: &[], scope: Scope::Java,
+        apply: |options, _| options.java_args.push(OsString::from("-Xprof")),
+        help: "run with the java sampling profiler",
+    },
+    LauncherOption {
+        name: "--manage", aliases: &[], arity: Arity::Flag, argument: "", values: &[], scope: Scope::Java,
+        apply: |options, _| options.apply_profile("manage"),
+        help: "same as -Xprofile=manage (enable JMX management)",
+    },
+    LauncherOption {
+        name: "--headless", aliases: &[], arity: Arity::Flag, argument: "", values: &[], scope: Scope::Java,
+        apply: |options, _| options.apply_profile("headless"),
+        help: "same as -Xprofile=headless (run without a display)",
+    },
+    LauncherOption {
+        name: "-Xproperties", aliases: &[], arity: Arity::Flag, argument: "", values: &[], scope: Scope::JRuby,
+        apply: |options, _| options.program_args.push(OsString::from("--properties")),
+        help: "list JRuby properties (same as --properties)",
+    },
+];
+
+impl LauncherOption {
+    fn names(&self) -> impl Iterator<Item = &'static str> {
+        std::iter::once(self.name).chain(self.aliases.iter().copied())
+    }
+
+    pub(crate) fn usage(&self) -> String {
+        match self.arity {
+            Arity::Flag => self.name.to_string(),
+            Arity::Separate => format!("{} {}", self.name, self.argument),
+            Arity::Attached => format!("{}{}", self.name, self.argument),
+        }
+    }
+
+    /// Check a value against `values`.
+    pub fn validate(&self, value: &OsString) -> Result<(), String> {
+        let value = value.to_string_lossy();
+
+        if self.arity == Arity::Attached && value.is_empty() {
+            return Err(format!("{} needs a value, e.g. {}", self.name, self.usage()));
+        }
+        if !self.values.is_empty() && !self.values.contains(&value.as_ref()) {
+            return Err(format!("{} must be one of {} (not '{}')", self.name, self.values.join(", "), value));
+        }
+
+        Ok(())
+    }
+}
+
+/// Find the option `argument` names.  Also returns how much of `argument` is the name so
+/// callers can split off an attached value without going through a lossy string.
+pub fn lookup(argument: &str) -> Option<(&'static LauncherOption, usize)> {
+    let exact = LAUNCHER_OPTIONS.iter()
+        .find(|option| option.arity != Arity::Attached && option.names().any(|name| name == argument));
+
+    match exact {
+        Some(option) => Some((option, argument.len())),
+        None => LAUNCHER_OPTIONS.iter()
+            .filter(|option| option.arity == Arity::Attached)
+            .find(|option| argument.starts_with(option.name) && argument.len() > option.name.len())
+            .map(|option| (option, option.name.len())),
+    }
+}
+
+// JRuby's own long options.  Some are close enough to ours to look like typos.
+const JRUBY_LONG_OPTIONS: [&str; 14] = [
+    "--backtrace-limit", "--copyright", "--debug", "--disable", "--disable-gems", "--dump", "--enable",
+    "--help", "--jdb", "--properties", "--profile", "--verbose", "--version", "--yydebug",
+];
+
+/// JRuby options whose value is the next argument, so that argument is not the script.
+pub const JRUBY_OPTIONS_WITH_VALUE: [&str; 6] = ["-C", "-E", "-F", "-I", "-e", "-r"];
+
+/// If `argument` looks like a mistyped launcher option return the option it was probably
+/// meant to be.  Only `--` and `-X` options are considered and -X JRuby properties
+/// (-Xcompile.mode=JIT) never are.
+pub fn suggest(argument: &str) -> Option<&'static str> {
+    let name = argument.split('=').next().unwrap_or(argument);
+
+    let candidate = if name.starts_with("--") {
+        !JRUBY_LONG_OPTIONS.contains(&name)
+    } else {
+        name.starts_with("-X") && !argument.contains('.') && !argument.contains('=')
+    };
+    if !candidate || name.len() <= 3 {
+        return None;
+    }
+
+    // Allow one typo in short names and two in longer ones.
+    let allowed = if name.len() <= 6 { 1 } else { 2 };
+
+    LAUNCHER_OPTIONS.iter()
+        .flat_map(|option| option.names())
+        .filter(|known| known.len() > 2)
+        .map(|known| (edit_distance(name, known), known))
+        .filter(|(distance, _)| *distance > 0 && *distance <= allowed)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, known)| known)
+}
+
+// Levenshtein distance.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + if a_char == *b_char { 0 } else { 1 };
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+/// Launcher section printed ahead of JRuby's own -X help, with any aliases from launcher.toml.
+pub fn help(aliases: &[(String, Alias)]) -> String {
+    let sections = [
+        (Scope::Launcher, "Launcher options:"),
+        (Scope::Java, "Options passed on to java:"),
+        (Scope::JRuby, "Options passed on to JRuby:"),
+    ];
+    let mut help = String::new();
+
+    for (scope, title) in sections.iter() {
+        help.push_str(title);
+        help.push('\n');
+
+        for option in LAUNCHER_OPTIONS.iter().filter(|option| option.scope == *scope) {
+            let mut text = option.help.to_string();
+
+            if !option.aliases.is_empty() {
+                text.push_str(&format!(" (also {})", option.aliases.join(", ")));
+            }
+            help.push_str(&format!("  {:<28} {}\n", option.usage(), text));
+        }
+        help.push('\n');
+    }
+
+    help.push_str(&format!("  {:<28} {}\n\n", "@<file>", "read arguments from <file> (@@ for a literal @)"));
+
+    if !aliases.is_empty() {
+        help.push_str("Aliases:\n");
+
+        for (name, alias) in aliases.iter() {
+            let usage = if alias.takes_value() { format!("{}=<value>", name) } else { name.clone() };
+            let text = match &alias.description {
+                Some(description) => description.clone(),
+                None => alias.expansion.join(" "),
+            };
+            help.push_str(&format!("  {:<28} {}\n", usage, text));
+        }
+        help.push('\n');
+    }
+
+    help.push_str("Environment variables:\n");
+    for (name, description) in ENVIRONMENT_VARIABLES.iter() {
+        help.push_str(&format!("  {:<28} {}\n", name, description));
+    }
+    help.push('\n');
+
+    help
+}
+
+/// Completion script for `shell` (one of COMPLETION_SHELLS).
+pub fn completion(shell: &str) -> String {
+    match shell {
+        "fish" => fish_completion(),
+        // zsh can run bash completion functions.
+        "zsh" => format!("autoload -U +X bashcompinit && bashcompinit\n{}", bash_completion()),
+        _ => bash_completion(),
+    }
+}
+
+fn bash_completion() -> String {
+    let words: Vec<&str> = LAUNCHER_OPTIONS.iter().flat_map(|option| option.names()).collect();
+    let mut value_cases = String::new();
+
+    for option in LAUNCHER_OPTIONS.iter().filter(|option| option.arity == Arity::Separate) {
+        let names: Vec<&str> = option.names().collect();
+        let reply = if option.values.is_empty() {
+            "compgen -f -- \"$cur\"".to_string()
+        } else {
+            format!("compgen -W \"{}\" -- \"$cur\"", option.values.join(" "))
+        };
+        value_cases.push_str(&format!("        {}) COMPREPLY=($({})); return ;;\n", names.join("|"), reply));
+    }
+
+    format!(r#"_jruby_launcher() {{
+    local cur="${{COMP_WORDS[COMP_CWORD]}}" prev="${{COMP_WORDS[COMP_CWORD-1]}}"
+    case "$prev" in
+{}    esac
+    if [[ "$cur" == -* ]]; then
+        COMPREPLY=($(compgen -W "{}" -- "$cur"))
+    else
+        COMPREPLY=($(compgen -f -- "$cur"))
+    fi
+}}
+complete -F _jruby_launcher jruby
+"#, value_cases, words.join(" "))
+}
+
+fn fish_completion() -> String {
+    let mut script = String::new();
+
+    for option in LAUNCHER_OPTIONS.iter().filter(|option| option.arity != Arity::Attached) {
+        for name in option.names() {
+            // fish spells single dash long options -o and double dash ones -l.
+            let flag = match name.strip_prefix("--") {
+                Some(long) => format!("-l '{}'", long),
+                None => format!("-o '{}'", &name[1..]),
+            };
+            let mut line = format!("complete -c jruby {} -d '{}'", flag, option.help.replace('\'', "\\'"));
+
+            if option.arity == Arity::Separate {
+                line.push_str(" -r");
+            }
+            if !option.values.is_empty() {
+                line.push_str(&format!(" -x -a '{}'", option.values.join(" ")));
+            }
+            script.push_str(&line);
+            script.push('\n');
+        }
+    }
+
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::ffi::OsString;
+    use crate::environment::ENVIRONMENT_VARIABLES;
+    use crate::launcher_config::Alias;
+    use crate::option_table::{completion, edit_distance, help, lookup, suggest, Arity, COMPLETION_SHELLS, LAUNCHER_OPTIONS};
+
+    #[test]
+    fn test_help_lists_everything() {
+        let help = help(&[]);
+
+        for option in LAUNCHER_OPTIONS.iter() {
+            for name in option.names() {
+                assert!(help.contains(name), "missing {}", name);
+            }
+        }
+        for (name, _) in ENVIRONMENT_VARIABLES.iter() {
+            assert!(help.contains(name), "missing {}", name);
+        }
+        assert!(help.contains("-Xjdkhome <dir>"));
+        assert!(help.contains("-J<option>"));
+        assert!(help.contains("(also -X)"));
+        assert!(!help.contains("Aliases:"));
+    }
+
+    #[test]
+    fn test_help_lists_aliases() {
+        let heap = Alias { expansion: vec!["-J-Xmx{}".to_string()], description: None };
+        let sql = Alias { expansion: vec!["-J-Dsql.trace=true".to_string()], description: Some("log every query".to_string()) };
+        let help = help(&[("--heap".to_string(), heap), ("--trace-sql".to_string(), sql)]);
+
+        assert!(help.contains("Aliases:\n  --heap=<value>"));
+        assert!(help.contains("-J-Xmx{}\n"));
+        assert!(help.contains("log every query\n"));
+    }
+
+    #[test]
+    fn test_table_is_consistent() {
+        let mut seen = HashSet::new();
+
+        for option in LAUNCHER_OPTIONS.iter() {
+            for name in option.names() {
+                assert!(seen.insert(name), "{} is in the table twice", name);
+            }
+            assert_eq!(option.arity == Arity::Flag, option.argument.is_empty(), "{}", option.name);
+            assert!(!option.help.is_empty(), "{}", option.name);
+        }
+    }
+
+    #[test]
+    fn test_lookup() {
+        assert_eq!(lookup("-Xtrace").map(|(o, len)| (o.name, len)), Some(("-Xtrace", 7)));
+        assert_eq!(lookup("-X").map(|(o, _)| o.name), Some("-Xhelp"));
+        assert_eq!(lookup("--no-bootclasspath").map(|(o, _)| o.name), Some("-Xnobootclasspath"));
+        assert_eq!(lookup("-J-classpath").map(|(o, _)| o.name), Some("-J-cp"));
+        assert_eq!(lookup("-Jea").map(|(o, _)| o.name), Some("-Jea"));
+        assert_eq!(lookup("-J-Xmx2g").map(|(o, len)| (o.name, len)), Some(("-J", 2)));
+        assert_eq!(lookup("-Xxss4m").map(|(o, len)| (o.name, len)), Some(("-Xxss", 5)));
+        assert!(lookup("-J").is_none());
+        assert!(lookup("-Xcompile.mode=JIT").is_none());
+        assert!(lookup("script.rb").is_none());
+    }
+
+    #[test]
+    fn test_validate() {
+        let (completion_option, _) = lookup("-Xcompletion").unwrap();
+
+        assert!(completion_option.validate(&OsString::from("bash")).is_ok());
+        assert!(completion_option.validate(&OsString::from("csh")).unwrap_err().contains("bash, zsh, fish"));
+        assert!(lookup("-Xxss4m").unwrap().0.validate(&OsString::new()).is_err());
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("--ng-sever", "--ng-server"), 1);
+        assert_eq!(edit_distance("-Xjdkhom", "-Xjdkhome"), 1);
+        assert_eq!(edit_distance("--dve", "--dev"), 2);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn test_suggest() {
+        assert_eq!(suggest("--ng-sever"), Some("--ng-server"));
+        assert_eq!(suggest("-Xjdkhom"), Some("-Xjdkhome"));
+        assert_eq!(suggest("--nocahce"), Some("--nocache"));
+        assert_eq!(suggest("--headles=true"), Some("--headless"));
+        assert_eq!(suggest("--ng-server"), None);
+        assert_eq!(suggest("--properties"), None);
+        assert_eq!(suggest("-Xcompile.mode=JIT"), None);
+        assert_eq!(suggest("--disable-gems"), None);
+        assert_eq!(suggest("-e"), None);
+        assert_eq!(suggest("script.rb"), None);
+    }
+
+    #[test]
+    fn test_completion() {
+        let bash = completion("bash");
+
+        assert!(bash.contains("-Xjdkhome"));
+        assert!(bash.contains("-Xcompletion) COMPREPLY=($(compgen -W \"bash zsh fish\""));
+        assert!(completion("zsh").contains("bashcompinit"));
+        assert!(completion("fish").contains("complete -c jruby -l 'ng-server'"));
+        assert_eq!(COMPLETION_SHELLS.len(), 3);
+    }
+}
@@ -23,6 +23,9 @@ pub trait OsStringExt {
     fn split_at(&self, index: usize) -> (OsString, OsString);
     fn split_ascii_whitespace(&self) -> OsWhitespaceSplitIter;
     fn starts_with(&self, string: OsString) -> bool;
+    fn shell_split(&self) -> Result<Vec<OsString>, String>;
+    fn shell_split_commented(&self) -> Result<Vec<OsString>, String>;
+    fn shell_quote(&self) -> OsString;
 }
 
 impl OsStringExt for OsString {
@@ -114,6 +117,22 @@ impl OsStringExt for OsString {
         }
     }
 
+    fn shell_split(&self) -> Result<Vec<OsString>, String> {
+        let words = shell_words(&to_units(self), false)?;
+
+        Ok(words.iter().map(|word| from_units(word)).collect())
+    }
+
+    fn shell_split_commented(&self) -> Result<Vec<OsString>, String> {
+        let words = shell_words(&to_units(self), true)?;
+
+        Ok(words.iter().map(|word| from_units(word)).collect())
+    }
+
+    fn shell_quote(&self) -> OsString {
+        from_units(&shell_quoted(&to_units(self)))
+    }
+
     #[cfg(windows)]
     fn starts_with(&self, string: OsString) -> bool {
         let vec: Vec<u16> = self.encode_wide().collect();
@@ -131,6 +150,139 @@ impl OsStringExt for OsString {
     }
 }
 
+// Units are u8 on unix and u16 on windows so these convert OsString without being lossy.
+#[cfg(windows)]
+fn to_units(string: &OsString) -> Vec<u16> {
+    string.encode_wide().collect()
+}
+
+#[cfg(not(windows))]
+fn to_units(string: &OsString) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    string.as_bytes().to_vec()
+}
+
+#[cfg(windows)]
+fn from_units(units: &[u16]) -> OsString {
+    use std::os::windows::ffi::OsStringExt;
+    OsString::from_wide(units)
+}
+
+#[cfg(not(windows))]
+fn from_units(units: &[u8]) -> OsString {
+    use std::os::unix::ffi::OsStringExt;
+    OsString::from_vec(units.to_vec())
+}
+
+fn is_ascii<T: Copy + Into<u32>>(unit: T, ascii: u8) -> bool {
+    unit.into() == ascii as u32
+}
+
+fn is_shell_whitespace<T: Copy + Into<u32>>(unit: T) -> bool {
+    matches!(unit.into(), 0x20 | 0x09 | 0x0A | 0x0B | 0x0C | 0x0D)
+}
+
+// Backslash separates path components on windows so outside of quotes it only escapes quotes.
+fn is_escapable<T: Copy + Into<u32>>(unit: T) -> bool {
+    !cfg!(windows) || is_ascii(unit, b'"') || is_ascii(unit, b'\'')
+}
+
+/// Split `units` into words like a POSIX shell would (minus expansions).  Single quotes keep
+/// everything literally, double quotes allow backslash escapes of $ ` " \ and newline, and an
+/// unquoted backslash escapes the next character.  With `comments` a # starting a word
+/// comments out the rest of the line.
+fn shell_words<T: Copy + Into<u32>>(units: &[T], comments: bool) -> Result<Vec<Vec<T>>, String> {
+    let mut words = vec![];
+    let mut word: Option<Vec<T>> = None; // Some even when empty so '' is still a word
+    let mut quote: Option<(T, usize)> = None;
+    let mut i = 0;
+
+    while i < units.len() {
+        let unit = units[i];
+        let next = units.get(i + 1).copied();
+
+        match quote {
+            Some((open, _)) if is_ascii(open, b'\'') => {
+                if is_ascii(unit, b'\'') {
+                    quote = None;
+                } else {
+                    word.get_or_insert_with(Vec::new).push(unit);
+                }
+            }
+            Some(_) => {
+                if is_ascii(unit, b'"') {
+                    quote = None;
+                } else if is_ascii(unit, b'\\') && matches!(next, Some(next) if b"$`\"\\\n".iter().any(|c| is_ascii(next, *c))) {
+                    if !is_ascii(next.unwrap(), b'\n') {
+                        word.get_or_insert_with(Vec::new).push(next.unwrap());
+                    }
+                    i += 1;
+                } else {
+                    word.get_or_insert_with(Vec::new).push(unit);
+                }
+            }
+            None if comments && word.is_none() && is_ascii(unit, b'#') => {
+                while i + 1 < units.len() && !is_ascii(units[i + 1], b'\n') {
+                    i += 1;
+                }
+            }
+            None if is_shell_whitespace(unit) => {
+                if let Some(word) = word.take() {
+                    words.push(word);
+                }
+            }
+            None if is_ascii(unit, b'\'') || is_ascii(unit, b'"') => {
+                word.get_or_insert_with(Vec::new);
+                quote = Some((unit, i));
+            }
+            None if is_ascii(unit, b'\\') && matches!(next, Some(next) if is_escapable(next)) => {
+                // backslash newline just continues the line
+                if !is_ascii(next.unwrap(), b'\n') {
+                    word.get_or_insert_with(Vec::new).push(next.unwrap());
+                }
+                i += 1;
+            }
+            None => word.get_or_insert_with(Vec::new).push(unit),
+        }
+
+        i += 1;
+    }
+
+    if let Some((open, offset)) = quote {
+        let open = if is_ascii(open, b'"') { '"' } else { '\'' };
+        return Err(format!("unbalanced {} quote starting at offset {}", open, offset));
+    }
+
+    if let Some(word) = word {
+        words.push(word);
+    }
+
+    Ok(words)
+}
+
+/// Quote `units` so shell_words gives them back as one word.  Plain words are left alone.
+fn shell_quoted<T: Copy + Into<u32> + From<u8>>(units: &[T]) -> Vec<T> {
+    let special = |unit: &T| is_shell_whitespace(*unit) || is_ascii(*unit, b'\'') || is_ascii(*unit, b'"')
+        || (is_ascii(*unit, b'\\') && !cfg!(windows));
+
+    if !units.is_empty() && !units.iter().any(special) {
+        return units.to_vec();
+    }
+
+    let mut quoted = vec![T::from(b'\'')];
+    for unit in units {
+        if is_ascii(*unit, b'\'') {
+            // close the quote, add an escaped ' and reopen it
+            quoted.extend(b"'\\''".iter().map(|c| T::from(*c)));
+        } else {
+            quoted.push(*unit);
+        }
+    }
+    quoted.push(T::from(b'\''));
+
+    quoted
+}
+
 #[cfg(windows)] const SPACE: u16 = b' ' as u16;
 #[cfg(windows)] const RETURN: u16 = b'\r' as u16;
 #[cfg(windows)] const TAB: u16 = b'\t' as u16;
@@ -365,6 +517,53 @@ mod tests {
     #[test]
     fn starts_with_simple() {
         assert!(OsString::from("-Xpotato").starts_with(OsString::from("-X")));
-        assert_eq!(false, OsString::from("-Xpotato").starts_with(OsString::from("-D")));
+        assert!(!OsString::from("-Xpotato").starts_with(OsString::from("-D")));
+    }
+
+    fn shell_split(string: &str) -> Result<Vec<OsString>, String> {
+        OsString::from(string).shell_split()
+    }
+
+    #[test]
+    fn shell_split_simple() {
+        assert_eq!(shell_split("").unwrap(), Vec::<OsString>::new());
+        assert_eq!(shell_split("  -Xmx2g \t -server\n").unwrap(), vec!["-Xmx2g", "-server"]);
+    }
+
+    #[test]
+    fn shell_split_quotes() {
+        assert_eq!(shell_split("-Dapp.name=\"My App\" -Da=b").unwrap(), vec!["-Dapp.name=My App", "-Da=b"]);
+        assert_eq!(shell_split("'it''s' \"a 'b'\" '\\\"'").unwrap(), vec!["its", "a 'b'", "\\\""]);
+        assert_eq!(shell_split("'' \"\"").unwrap(), vec!["", ""]);
+        assert_eq!(shell_split("\"a\\\"b\\c\"").unwrap(), vec!["a\"b\\c"]);
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn shell_split_backslash() {
+        assert_eq!(shell_split("My\\ App a\\\\b c\\\nd e\\").unwrap(), vec!["My App", "a\\b", "cd", "e\\"]);
+    }
+
+    #[test]
+    fn shell_split_unbalanced() {
+        assert_eq!(shell_split("-Dapp.name=\"My App").unwrap_err(), "unbalanced \" quote starting at offset 11");
+        assert!(shell_split("'oops").unwrap_err().contains("unbalanced '"));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn shell_split_comments() {
+        let contents = OsString::from("# heap\n-Xmx2g # trailing\n-Dx=a#b '# not a comment'\n");
+
+        assert_eq!(contents.shell_split_commented().unwrap(), vec!["-Xmx2g", "-Dx=a#b", "# not a comment"]);
+        assert_eq!(shell_split("# kept").unwrap(), vec!["#", "kept"]);
+    }
+
+    #[test]
+    fn shell_quote_round_trip() {
+        for word in &["plain", "", "My App", "it's", "a\"b", "$HOME", "-Dx=\\y"] {
+            let quoted = OsString::from(word).shell_quote();
+            assert_eq!(quoted.shell_split().unwrap(), vec![*word], "{:?}", quoted);
+        }
+        assert_eq!(OsString::from("plain").shell_quote(), "plain");
+    }
+}
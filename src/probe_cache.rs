@@ -0,0 +1,248 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use log::info;
+use crate::environment::Environment;
+use crate::java_installation::JavaInstallation;
+use crate::jdk_discovery;
+use crate::jdk_discovery::DiscoveredJdk;
+use crate::jruby_installation::JRubyInfo;
+use crate::launch_options::{JavaVersion, JSA_DIR};
+
+/// On-disk cache of what we learned about a JDK so short scripts do not pay for
+/// reading its release file, scanning for CDS archives or (worst case) running java.
+///
+/// There is one file per JDK home.  Its first lines record the JDK path and the mtimes of
+/// the release file, the modules image and the server VM directory.  If any of those no
+/// longer match then the entry is stale and gets replaced.  The jruby jar gets an entry too, keyed on its path and
+/// mtime, so we do not open it as a zip on every launch.  JDK discovery gets one keyed on
+/// the search roots and the release file of every JDK in them.
+///
+/// Whether a JDK can run here depends on the machine so keys include the launcher's target.
+pub(crate) struct ProbeCache {
+    dir: PathBuf,
+}
+
+impl ProbeCache {
+    pub(crate) fn new(env: &Environment) -> Option<ProbeCache> {
+        let cache_home = match (&env.xdg_cache_home, &env.home) {
+            (Some(cache_home), _) => PathBuf::from(cache_home),
+            (None, Some(home)) => PathBuf::from(home).join(".cache"),
+            (None, None) => return None,
+        };
+
+        Some(ProbeCache { dir: cache_home.join("jruby-launcher").join("probe") })
+    }
+
+    pub(crate) fn load(&self, java_home: &Path) -> Option<JavaInstallation> {
+        self.read(&self.entry(java_home), &ProbeCache::key(java_home))
+            .map(|values| JavaInstallation::from_cache(java_home, &values))
+    }
+
+    pub(crate) fn store(&self, java_home: &Path, installation: &JavaInstallation) {
+        self.write(&self.entry(java_home), &ProbeCache::key(java_home), &installation.to_cache());
+    }
+
+    pub(crate) fn load_jruby(&self, jar: &Path) -> Option<JRubyInfo> {
+        self.read(&self.jruby_entry(jar), &ProbeCache::jruby_key(jar))
+            .map(|values| JRubyInfo::from_cache(&values))
+    }
+
+    pub(crate) fn store_jruby(&self, jar: &Path, info: &JRubyInfo) {
+        self.write(&self.jruby_entry(jar), &ProbeCache::jruby_key(jar), &info.to_cache());
+    }
+
+    pub(crate) fn load_discovery(&self, roots: &[PathBuf]) -> Option<Vec<DiscoveredJdk>> {
+        let values = self.read(&self.dir.join("discovery"), &ProbeCache::discovery_key(roots))?;
+        let mut jdks = vec![];
+
+        for line in values.lines() {
+            let (version, home) = line.strip_prefix("JDK=")?.trim_matches('"').split_once(' ')?;
+            jdks.push(DiscoveredJdk { home: PathBuf::from(home), version: JavaVersion::parse(version)? });
+        }
+
+        Some(jdks)
+    }
+
+    pub(crate) fn store_discovery(&self, roots: &[PathBuf], jdks: &[DiscoveredJdk]) {
+        let values: Vec<String> = jdks.iter()
+            .map(|jdk| format!("JDK=\"{} {}\"", jdk.version, jdk.home.display()))
+            .collect();
+
+        self.write(&self.dir.join("discovery"), &ProbeCache::discovery_key(roots), &values.join("\n"));
+    }
+
+    fn read(&self, file: &Path, expected_key: &str) -> Option<String> {
+        let contents = fs::read_to_string(file).ok()?;
+        let (key, values) = contents.split_once("\n\n")?;
+
+        if key != expected_key {
+            info!("Probe cache entry {:?} is stale", file);
+            return None;
+        }
+
+        info!("Using probe cache entry {:?}", file);
+        Some(values.to_string())
+    }
+
+    // Failures here just mean we probe again next time.
+    fn write(&self, file: &Path, key: &str, values: &str) {
+        let temp = file.with_extension(format!("tmp{}", std::process::id()));
+        let contents = format!("{}\n\n{}\n", key, values);
+
+        let result = fs::create_dir_all(&self.dir)
+            .and_then(|_| fs::write(&temp, contents))
+            // rename so concurrent launches never see a half written entry.
+            .and_then(|_| fs::rename(&temp, file));
+
+        match result {
+            Ok(_) => info!("Stored probe cache entry {:?}", file),
+            Err(e) => {
+                info!("Unable to store probe cache entry {:?}: {}", file, e);
+                let _ = fs::remove_file(&temp);
+            }
+        }
+    }
+
+    fn entry(&self, java_home: &Path) -> PathBuf {
+        self.dir.join(format!("{:016x}", fnv1a(java_home.to_string_lossy().as_bytes())))
+    }
+
+    fn jruby_entry(&self, jar: &Path) -> PathBuf {
+        self.dir.join(format!("jruby-{:016x}", fnv1a(jar.to_string_lossy().as_bytes())))
+    }
+
+    fn jruby_key(jar: &Path) -> String {
+        format!("JAR={}\nJAR_MTIME={}", jar.display(), mtime(jar))
+    }
+
+    // Installing or removing a JDK changes its root's mtime and updating one its release file.
+    fn discovery_key(roots: &[PathBuf]) -> String {
+        let mut lines = vec![format!("HOST={}", env!("JRUBY_LAUNCHER_TARGET"))];
+
+        for root in roots {
+            lines.push(format!("ROOT={} MTIME={}", root.display(), mtime(root)));
+
+            for candidate in jdk_discovery::candidates_in(root) {
+                lines.push(format!("RELEASE={} MTIME={}", candidate.display(), mtime(&candidate.join("release"))));
+            }
+        }
+
+        lines.join("\n")
+    }
+
+    // An upgrade in place always rewrites lib/modules, even when there is no release file.
+    fn key(java_home: &Path) -> String {
+        format!("HOST={}\nHOME={}\nRELEASE_MTIME={}\nMODULES_MTIME={}\nSERVER_MTIME={}",
+                env!("JRUBY_LAUNCHER_TARGET"),
+                java_home.display(),
+                mtime(&java_home.join("release")),
+                mtime(&java_home.join("lib").join("modules")),
+                mtime(&java_home.join(JSA_DIR).join("server")))
+    }
+}
+
+fn mtime(path: &Path) -> String {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|since| format!("{}.{:09}", since.as_secs(), since.subsec_nanos()))
+        .unwrap_or_else(|| "none".to_string())
+}
+
+// Stable across builds unlike std's DefaultHasher.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325u64, |hash, b| (hash ^ *b as u64).wrapping_mul(0x100000001b3))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::time::{Duration, UNIX_EPOCH};
+    use crate::file_helper::tests::temp_dir;
+    use crate::environment::tests::empty_env;
+    use crate::java_installation::JavaInstallation;
+    use crate::jruby_installation::JRubyInfo;
+    use crate::probe_cache::ProbeCache;
+
+    #[test]
+    fn test_probe_cache_invalidates_on_change() {
+        let root = temp_dir("probe_cache");
+        let jdk = root.join("jdk");
+        fs::create_dir_all(&jdk).unwrap();
+        fs::write(jdk.join("release"), "JAVA_VERSION=\"17.0.2\"\n").unwrap();
+
+        let mut env = empty_env();
+        env.xdg_cache_home = Some(root.join("cache").into_os_string());
+        let cache = ProbeCache::new(&env).unwrap();
+
+        assert!(cache.load(&jdk).is_none());
+
+        cache.store(&jdk, &JavaInstallation::from_home(&jdk));
+        let cached = cache.load(&jdk).map(|java| java.java_version.unwrap().to_string());
+
+        // a new release file means a new JDK
+        fs::remove_file(jdk.join("release")).unwrap();
+        let stale = cache.load(&jdk);
+
+        assert_eq!(cached, Some("17.0.2".to_string()));
+        assert!(stale.is_none());
+    }
+
+    #[test]
+    fn test_probe_cache_without_release_file() {
+        let root = temp_dir("probe_cache_modules");
+        let jdk = root.join("jdk");
+        fs::create_dir_all(jdk.join("lib")).unwrap();
+        fs::write(jdk.join("lib").join("modules"), "old").unwrap();
+
+        let mut env = empty_env();
+        env.xdg_cache_home = Some(root.join("cache").into_os_string());
+        let cache = ProbeCache::new(&env).unwrap();
+
+        cache.store(&jdk, &JavaInstallation::from_home(&jdk));
+        let cached = cache.load(&jdk);
+
+        // an upgrade in place rewrites the modules image
+        let modules = fs::File::options().write(true).open(jdk.join("lib").join("modules")).unwrap();
+        modules.set_modified(UNIX_EPOCH + Duration::from_secs(1)).unwrap();
+        let stale = cache.load(&jdk);
+
+        assert!(cached.is_some());
+        assert!(stale.is_none());
+    }
+
+    #[test]
+    fn test_probe_cache_jruby_jar() {
+        let root = temp_dir("probe_cache_jruby");
+        let jar = root.join("jruby.jar");
+        fs::write(&jar, "first").unwrap();
+
+        let mut env = empty_env();
+        env.xdg_cache_home = Some(root.join("cache").into_os_string());
+        let cache = ProbeCache::new(&env).unwrap();
+        let info = JRubyInfo { version: Some("9.4.8.0".to_string()), minimum_java: Some(8) };
+
+        let empty = cache.load_jruby(&jar);
+        cache.store_jruby(&jar, &info);
+        let cached = cache.load_jruby(&jar);
+
+        // a replaced jar is a new JRuby
+        fs::remove_file(&jar).unwrap();
+        let stale = cache.load_jruby(&jar);
+
+        assert!(empty.is_none());
+        assert_eq!(cached, Some(info));
+        assert!(stale.is_none());
+    }
+
+    #[test]
+    fn test_probe_cache_location() {
+        let mut env = empty_env();
+        assert!(ProbeCache::new(&env).is_none());
+
+        env.home = Some("/home/user".into());
+        assert!(ProbeCache::new(&env).unwrap().dir.ends_with(".cache/jruby-launcher/probe"));
+    }
+}
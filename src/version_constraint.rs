@@ -0,0 +1,130 @@
+use core::fmt;
+use std::fmt::Formatter;
+use crate::launch_options::JavaVersion;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Prefix,
+    Equal,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+}
+
+#[derive(Debug, Clone)]
+struct Clause {
+    op: Op,
+    version: JavaVersion,
+}
+
+impl Clause {
+    fn matches(&self, version: &JavaVersion) -> bool {
+        match self.op {
+            Op::Prefix => {
+                let components = version.components();
+                self.version.components().iter().enumerate()
+                    .all(|(i, n)| components.get(i).copied().unwrap_or(0) == *n)
+            }
+            Op::Equal => version == &self.version,
+            Op::Greater => version > &self.version,
+            Op::GreaterEqual => version >= &self.version,
+            Op::Less => version < &self.version,
+            Op::LessEqual => version <= &self.version,
+        }
+    }
+}
+
+/// A set of comma separated version requirements which must all hold (e.g. `>=17,<22`).
+///
+/// A bare version is a prefix match: `21` accepts any 21.x and `17.0` any 17.0.x.
+/// `=21.0.2` requires that exact version.
+#[derive(Debug, Clone)]
+pub struct VersionConstraint {
+    raw: String,
+    clauses: Vec<Clause>,
+}
+
+impl VersionConstraint {
+    pub fn parse(constraint: &str) -> Option<VersionConstraint> {
+        let mut clauses = vec![];
+
+        for clause in constraint.split(',').map(|c| c.trim()) {
+            let (op, version) = if let Some(version) = clause.strip_prefix(">=") {
+                (Op::GreaterEqual, version)
+            } else if let Some(version) = clause.strip_prefix("<=") {
+                (Op::LessEqual, version)
+            } else if let Some(version) = clause.strip_prefix("==") {
+                (Op::Equal, version)
+            } else if let Some(version) = clause.strip_prefix('>') {
+                (Op::Greater, version)
+            } else if let Some(version) = clause.strip_prefix('<') {
+                (Op::Less, version)
+            } else if let Some(version) = clause.strip_prefix('=') {
+                (Op::Equal, version)
+            } else {
+                (Op::Prefix, clause)
+            };
+
+            clauses.push(Clause { op, version: JavaVersion::parse(version.trim())? });
+        }
+
+        Some(VersionConstraint { raw: constraint.trim().to_string(), clauses })
+    }
+
+    pub fn matches(&self, version: &JavaVersion) -> bool {
+        self.clauses.iter().all(|clause| clause.matches(version))
+    }
+}
+
+impl fmt::Display for VersionConstraint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::launch_options::JavaVersion;
+    use crate::version_constraint::VersionConstraint;
+
+    fn matches(constraint: &str, version: &str) -> bool {
+        VersionConstraint::parse(constraint).unwrap().matches(&JavaVersion::parse(version).unwrap())
+    }
+
+    #[test]
+    fn test_constraint_range() {
+        assert!(matches(">=17,<22", "17"));
+        assert!(matches(">=17,<22", "21.0.2+13"));
+        assert!(!matches(">=17,<22", "22"));
+        assert!(!matches(">=17,<22", "11.0.14"));
+        assert!(!matches(">=17, <22", "1.8.0_292"));
+        assert!(matches(">11", "11.0.1"));
+        assert!(matches("<=21", "21"));
+    }
+
+    #[test]
+    fn test_constraint_prefix() {
+        assert!(matches("21", "21"));
+        assert!(matches("21", "21.0.2"));
+        assert!(matches("21", "21-ea"));
+        assert!(!matches("21", "22"));
+        assert!(matches("17.0", "17.0.9"));
+        assert!(!matches("17.0.2", "17.0.9"));
+        assert!(matches("8", "1.8.0_292"));
+    }
+
+    #[test]
+    fn test_constraint_exact() {
+        assert!(matches("=21.0.2", "21.0.2"));
+        assert!(!matches("=21.0.2", "21.0.3"));
+        assert!(matches("==21", "21.0.0"));
+    }
+
+    #[test]
+    fn test_constraint_invalid() {
+        assert!(VersionConstraint::parse("").is_none());
+        assert!(VersionConstraint::parse(">=seventeen").is_none());
+        assert!(VersionConstraint::parse(">=17,").is_none());
+    }
+}